@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use toml_edit::{Array, InlineTable};
+
+use crate::{
+    config::Config,
+    generator::{data::EmbassyCrates, lock::Lock},
+    git::Git,
+    project::{read_manifest, table_mut, upsert_dependency, write_manifest, Project},
+};
+
+#[derive(Parser)]
+pub struct AddCommand {
+    /// Embassy crate to add
+    #[clap(value_enum)]
+    krate: EmbassyCrates,
+    /// Features to enable, comma separated
+    #[clap(short = 'F', long, value_delimiter = ',')]
+    features: Vec<String>,
+    /// Path to the project
+    #[clap(short, long, default_value = ".")]
+    path: PathBuf,
+}
+
+pub async fn add(cmd: AddCommand) -> anyhow::Result<()> {
+    let project = Project::discover(&cmd.path)?;
+    let name = cmd.krate.name();
+    let rev = project.pinned_rev()?;
+    let version = Git::get_crate_version(name, rev.as_deref().unwrap_or("main")).await?;
+
+    let mut entry = InlineTable::new();
+    entry.insert("version", version.as_str().into());
+    if !cmd.features.is_empty() {
+        entry.insert("features", Array::from_iter(&cmd.features).into());
+    }
+
+    let root_manifest = project.root_manifest();
+    let mut root = read_manifest(&root_manifest)?;
+    let mut manifests = vec![root_manifest.clone()];
+    if project.workspace {
+        manifests.push(project.app_manifest()?);
+    }
+    // rewritten files that were still as generated keep counting as generated in the lock
+    let mut lock = Lock::read_if_exists(&project.root)?;
+    let generated = manifests
+        .iter()
+        .map(|path| path.strip_prefix(&project.root).unwrap_or(path).to_owned())
+        .filter(|file| {
+            lock.as_ref()
+                .is_some_and(|lock| lock.is_generated(&project.root, file))
+        })
+        .collect::<Vec<_>>();
+
+    if project.workspace {
+        upsert_dependency(
+            table_mut(&mut root, &["workspace", "dependencies"])?,
+            name,
+            entry,
+        );
+
+        let app_manifest = project.app_manifest()?;
+        let mut app = read_manifest(&app_manifest)?;
+        let mut member_entry = InlineTable::new();
+        member_entry.insert("workspace", true.into());
+        upsert_dependency(table_mut(&mut app, &["dependencies"])?, name, member_entry);
+        write_manifest(&app_manifest, &app)?;
+    } else {
+        upsert_dependency(table_mut(&mut root, &["dependencies"])?, name, entry);
+    }
+
+    let mut patch = InlineTable::new();
+    let upstream = match project.upstream()? {
+        Some(upstream) => upstream,
+        None => Config::load()?.upstream.unwrap_or_default(),
+    };
+    patch.insert("git", upstream.into());
+    if let Some(rev) = &rev {
+        patch.insert("rev", rev.as_str().into());
+    }
+    table_mut(&mut root, &["patch", "crates-io"])?.insert(name, toml_edit::value(patch));
+    write_manifest(&root_manifest, &root)?;
+
+    // `regen` adds the crate again
+    if let Some(lock) = &mut lock {
+        for file in &generated {
+            lock.rehash(&project.root, file)?;
+        }
+        let inputs = &mut lock.inputs;
+        if !inputs.crates.iter().any(|c| c == name) {
+            inputs.crates.push(name.into());
+        }
+        for feature in &cmd.features {
+            let feature = format!("{name}/{feature}");
+            if !inputs.features.contains(&feature) {
+                inputs.features.push(feature);
+            }
+        }
+        lock.resolved.crates.insert(name.into(), version.clone());
+        lock.write(&project.root)?;
+    }
+
+    println!("Added {name} v{version}");
+
+    Ok(())
+}
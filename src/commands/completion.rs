@@ -1,19 +1,191 @@
-use clap::{CommandFactory, Parser};
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate, Generator, Shell};
 
-use crate::Cli;
+use crate::{commands::create::Vendor, generator::data::DATA, Cli};
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CompletionCommand {
+    #[command(subcommand)]
+    action: Option<CompletionAction>,
     /// Shell type to generate completions for
-    pub shell: Shell,
+    #[clap(required = true)]
+    pub shell: Option<Shell>,
+}
+
+#[derive(Subcommand)]
+enum CompletionAction {
+    /// Install completions where the shell loads them from
+    Install {
+        /// Shell type to install completions for
+        shell: Shell,
+    },
+}
+
+/// prints MCU names for the completion scripts, not meant to be used directly
+#[derive(Parser)]
+pub struct CompleteMcuCommand {
+    /// Only complete MCUs of this vendor
+    #[clap(short, long)]
+    vendor: Option<Vendor>,
+    /// Partial MCU name
+    #[clap(default_value = "")]
+    prefix: String,
+}
+
+const BASH_DYNAMIC: &str = r#"
+_embassy-cli_dynamic() {
+    local cur prev vendor i
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+
+    if [[ "$prev" == "--mcu" || "$prev" == "-m" || ( "${COMP_WORDS[1]}" == "info" && "$cur" != -* ) ]]; then
+        for ((i = 1; i < COMP_CWORD - 1; i++)); do
+            if [[ "${COMP_WORDS[i]}" == "--vendor" || "${COMP_WORDS[i]}" == "-v" ]]; then
+                vendor="${COMP_WORDS[i+1]}"
+            fi
+        done
+        COMPREPLY=($(embassy-cli complete-mcu ${vendor:+--vendor "$vendor"} -- "$cur" 2>/dev/null))
+        return 0
+    fi
+
+    _embassy-cli "$@"
+}
+
+complete -F _embassy-cli_dynamic -o bashdefault -o default embassy-cli
+"#;
+
+/// installed as `_embassy-cli_dynamic`, so when zsh autoloads it the clap script only registers
+/// itself and this wrapper answers the first completion too
+const ZSH_DYNAMIC: &str = r#"
+_embassy-cli_mcus() {
+    local vendor i
+    local -a mcus
+    for ((i = 2; i < CURRENT; i++)); do
+        case $words[i] in
+            -v|--vendor) vendor=$words[i+1] ;;
+            --vendor=*) vendor=${words[i]#--vendor=} ;;
+        esac
+    done
+    mcus=(${(f)"$(embassy-cli complete-mcu ${vendor:+--vendor $vendor} 2>/dev/null)"})
+    compadd -a mcus
+}
+
+_embassy-cli_dynamic() {
+    if [[ $words[CURRENT-1] == (-m|--mcu) || ( $words[2] == info && $PREFIX != -* ) ]]; then
+        _embassy-cli_mcus
+    elif compset -P '--mcu='; then
+        _embassy-cli_mcus
+    else
+        _embassy-cli "$@"
+    fi
 }
 
+compdef _embassy-cli_dynamic embassy-cli
+if [ "$funcstack[1]" = "_embassy-cli_dynamic" ]; then
+    _embassy-cli_dynamic "$@"
+fi
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+function __embassy_cli_vendor
+    set -l tokens (commandline -opc)
+    for i in (seq (count $tokens))
+        if contains -- $tokens[$i] --vendor -v; and test $i -lt (count $tokens)
+            echo --vendor
+            echo $tokens[(math $i + 1)]
+        end
+    end
+end
+
+complete -c embassy-cli -n "__fish_seen_subcommand_from create" -s m -l mcu -r -f -a "(embassy-cli complete-mcu (__embassy_cli_vendor) -- (commandline -ct) 2>/dev/null)"
+complete -c embassy-cli -n "__fish_seen_subcommand_from info" -f -a "(embassy-cli complete-mcu -- (commandline -ct) 2>/dev/null)"
+"#;
+
 fn print_completions<G: Generator>(gen: G, cmd: &mut clap::Command) {
     generate(gen, cmd, cmd.get_name().to_string(), &mut std::io::stdout());
 }
 
-pub fn completions(cmd: CompletionCommand) {
+/// static clap completions, extended with MCU name completion for the shells that support it
+fn script(shell: Shell) -> anyhow::Result<String> {
     let mut cli = Cli::command();
-    print_completions(cmd.shell, &mut cli);
+    let mut buf = vec![];
+    generate(shell, &mut cli, "embassy-cli", &mut buf);
+    let script = String::from_utf8(buf)?;
+
+    Ok(match shell {
+        Shell::Bash => script + BASH_DYNAMIC,
+        Shell::Zsh => script + ZSH_DYNAMIC,
+        Shell::Fish => script + FISH_DYNAMIC,
+        _ => script,
+    })
+}
+
+pub fn completions(cmd: CompletionCommand) -> anyhow::Result<()> {
+    match (cmd.action, cmd.shell) {
+        (Some(CompletionAction::Install { shell }), _) => install(shell),
+        (None, Some(shell @ (Shell::Bash | Shell::Zsh | Shell::Fish))) => {
+            print!("{}", script(shell)?);
+            Ok(())
+        }
+        (None, Some(shell)) => {
+            print_completions(shell, &mut Cli::command());
+            Ok(())
+        }
+        (None, None) => unreachable!("clap requires a shell"),
+    }
+}
+
+fn install(shell: Shell) -> anyhow::Result<()> {
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or(anyhow!("HOME is not set"))?;
+    let xdg_dir = |var: &str, default: &str| {
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .unwrap_or(home.join(default))
+    };
+
+    let path = match shell {
+        Shell::Bash => xdg_dir("XDG_DATA_HOME", ".local/share")
+            .join("bash-completion/completions/embassy-cli"),
+        Shell::Zsh => home.join(".zfunc/_embassy-cli_dynamic"),
+        Shell::Fish => xdg_dir("XDG_CONFIG_HOME", ".config").join("fish/completions/embassy-cli.fish"),
+        _ => anyhow::bail!(
+            "Installing {shell} completions is not supported, redirect the output of `embassy-cli completion {shell}` instead"
+        ),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, script(shell)?)?;
+
+    println!("Installed {shell} completions to {}", path.display());
+    if shell == Shell::Zsh {
+        println!("Make sure ~/.zfunc is in your fpath by adding `fpath+=~/.zfunc` before `compinit` in ~/.zshrc");
+    }
+
+    Ok(())
+}
+
+pub fn complete_mcu(cmd: CompleteMcuCommand) -> anyhow::Result<()> {
+    let prefix = cmd.prefix.to_lowercase();
+    let vendors = match cmd.vendor {
+        Some(vendor) => vec![vendor],
+        None => Vendor::value_variants().to_vec(),
+    };
+
+    for vendor in vendors {
+        for mcu in DATA.mcu_list(vendor)? {
+            if mcu.starts_with(&prefix) {
+                println!("{mcu}");
+            }
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,85 @@
+use clap::{Parser, Subcommand};
+
+use crate::config::{self, Config, KEYS, PROJECT_CONFIG};
+
+#[derive(Parser)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    action: ConfigAction,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective value of a key
+    Get {
+        #[clap(value_parser = KEYS)]
+        key: String,
+    },
+    /// Set a key in the user config
+    Set {
+        #[clap(value_parser = KEYS)]
+        key: String,
+        value: String,
+        /// Set the key in the project config (.embassy-cli.toml) instead
+        #[clap(long)]
+        project: bool,
+    },
+    /// List every key with its effective value and where it is set
+    List,
+}
+
+pub fn config(cmd: ConfigCommand) -> anyhow::Result<()> {
+    match cmd.action {
+        ConfigAction::Get { key } => {
+            if let Some(value) = Config::load()?.get(&key)? {
+                println!("{value}");
+            }
+        }
+        ConfigAction::Set {
+            key,
+            value,
+            project,
+        } => {
+            let path = if project {
+                match config::project_path() {
+                    Some(path) => path,
+                    None => std::env::current_dir()?.join(PROJECT_CONFIG),
+                }
+            } else {
+                config::user_path()
+                    .ok_or(anyhow::anyhow!("Neither XDG_CONFIG_HOME nor HOME is set"))?
+            };
+
+            config::set(&path, &key, &value)?;
+            println!("Set {key} in {}", path.display());
+        }
+        ConfigAction::List => {
+            let user_path = config::user_path();
+            let project_path = config::project_path();
+            let user = match &user_path {
+                Some(path) => Config::read(path)?,
+                None => Config::default(),
+            };
+            let project = match &project_path {
+                Some(path) => Config::read(path)?,
+                None => Config::default(),
+            };
+            let builtin = Config::builtin();
+
+            for key in KEYS {
+                let (value, source) = if let Some(value) = project.get(key)? {
+                    (value, project_path.as_ref().unwrap().display().to_string())
+                } else if let Some(value) = user.get(key)? {
+                    (value, user_path.as_ref().unwrap().display().to_string())
+                } else if let Some(value) = builtin.get(key)? {
+                    (value, "default".into())
+                } else {
+                    ("-".into(), "unset".into())
+                };
+                println!("{key:<10} {value:<40} ({source})");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,27 @@
+use std::{
+    collections::BTreeMap,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
 use clap::{builder::PossibleValue, Parser, ValueEnum};
+use inquire::{validator::Validation, InquireError};
+use serde::Serialize;
+use serde_json::json;
 
-use crate::generator::{
-    data::DATA,
-    generate::{self, GeneratorConfig},
+use crate::{
+    config::{name_of, Config},
+    error::{ErrorKind, ValidationError},
+    generator::{
+        boards::Board,
+        clocks::{format_freq, parse_freq, Sysclk},
+        data::{DataError, EmbassyCrates, DATA},
+        generate::{self, GeneratorConfig},
+        lock::LOCK_FILE,
+        peripherals::{self, Peripheral},
+        templates::{crate_features, project_crates, CrateOptions, TemplateBuilder},
+    },
+    preset::Preset,
 };
 
 #[derive(Parser)]
@@ -16,12 +35,97 @@ pub struct CreateCommand {
     /// MCU
     #[clap(short, long)]
     mcu: Option<String>,
-    /// Do not pin to the latest commit of the Embassy crate
+    /// Development board, picks the MCU and writes a blinky and button example for its pins,
+    /// `list boards` shows the known boards
     #[clap(long)]
+    board: Option<String>,
+    /// Do not pin to the latest commit of the Embassy crate
+    #[clap(long, overrides_with = "pin")]
     no_pin: bool,
+    /// Pin to the latest commit of the Embassy crate (default)
+    #[clap(long, overrides_with = "no_pin")]
+    pin: bool,
     /// Create project in a workspace
-    #[clap(long)]
+    #[clap(long, overrides_with = "no_workspace")]
     workspace: bool,
+    /// Create a single crate project (default)
+    #[clap(long, overrides_with = "workspace")]
+    no_workspace: bool,
+    /// Tool used by `cargo run` to flash and run the firmware
+    #[clap(long, value_enum)]
+    runner: Option<Runner>,
+    /// Embassy git repository the crates are patched from, eg: a fork
+    /// (the revision, versions and toolchain are still resolved on embassy-rs/embassy)
+    #[clap(long)]
+    upstream: Option<String>,
+    /// Logging backend, none also drops the logging calls from main.rs
+    #[clap(long, value_enum)]
+    logging: Option<Logging>,
+    /// Log level, set through DEFMT_LOG with defmt and the crate features with log
+    #[clap(long, value_enum)]
+    log_level: Option<LogLevel>,
+    /// Panic handler, persist keeps the panic message in RAM across resets
+    #[clap(long, value_enum)]
+    panic: Option<Panic>,
+    /// Executors to run the tasks on, interrupt adds a higher priority one to the thread executor
+    #[clap(long, value_enum)]
+    executor: Option<Executor>,
+    /// Free interrupt the interrupt executor runs in, implies --executor interrupt
+    /// (default: from the chip data)
+    #[clap(long, value_name = "IRQ")]
+    executor_irq: Option<String>,
+    /// Size in bytes of the arena tasks are allocated from on stable, eg: 8192
+    #[clap(long, value_name = "BYTES")]
+    task_arena_size: Option<u32>,
+    /// Frequency of the HSE crystal, eg: 8MHz, the PLL runs from the HSI without it (STM32 only)
+    #[clap(long, value_name = "FREQ", value_parser = parse_freq)]
+    hse: Option<u32>,
+    /// System clock to set the PLL up for: max or a frequency, eg: 84MHz (STM32 only)
+    /// (default: max with --hse, the HAL defaults without)
+    #[clap(long, value_name = "max|FREQ", value_parser = Sysclk::parse)]
+    sysclk: Option<Sysclk>,
+    /// Peripheral driven from main.rs with an example task, repeatable: kind:INSTANCE with
+    /// optional pins and DMA channels, eg: uart:USART2 or spi:SPI1:sck=PA5,tx-dma=DMA2_CH3
+    #[clap(long, value_name = "SPEC", value_parser = Peripheral::parse)]
+    with_peripheral: Vec<Peripheral>,
+    /// Chip description in the stm32-data format, or a directory of them, peripherals are
+    /// checked against (default: from the config files)
+    #[clap(long, value_name = "PATH")]
+    chip_data: Option<PathBuf>,
+    /// Editor to generate settings for
+    #[clap(long, value_enum)]
+    editor: Option<Editor>,
+    /// SPDX license expression for the package, eg: "MIT OR Apache-2.0"
+    #[clap(long)]
+    license: Option<String>,
+    /// Toolchain to build with: stable, nightly or a channel, eg: "nightly-2024-04-14"
+    /// (default: stable when the embassy revision supports it)
+    #[clap(long)]
+    toolchain: Option<String>,
+    /// Extra embassy crates to depend on, comma separated
+    #[clap(short, long, value_enum, value_delimiter = ',')]
+    crates: Vec<EmbassyCrates>,
+    /// Extra crate features, comma separated, eg: "embassy-time/tick-hz-1_000"
+    #[clap(short = 'F', long, value_delimiter = ',')]
+    features: Vec<String>,
+    /// Time driver of the HAL, eg: "tim2" for time-driver-tim2 (default: from the chip data)
+    #[clap(long)]
+    time_driver: Option<String>,
+    /// Tick rate of embassy-time in Hz (default: from the chip data)
+    #[clap(long)]
+    tick_hz: Option<u64>,
+    /// Directory of files copied over the generated project
+    #[clap(long)]
+    template: Option<PathBuf>,
+    /// Name of a saved preset, or path to a preset file
+    #[clap(long)]
+    preset: Option<String>,
+    /// Never prompt, fail with the missing options instead (default without a terminal)
+    #[clap(short = 'y', long, visible_alias = "yes")]
+    non_interactive: bool,
+    /// Print a JSON report of the generated project, implies --non-interactive
+    #[clap(long)]
+    json: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -45,6 +149,55 @@ impl ValueEnum for Vendor {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Runner {
+    #[default]
+    ProbeRun,
+    ProbeRs,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Logging {
+    #[default]
+    Defmt,
+    Log,
+    None,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum LogLevel {
+    #[default]
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Panic {
+    #[default]
+    Probe,
+    Halt,
+    Reset,
+    Persist,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Executor {
+    #[default]
+    Thread,
+    Interrupt,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Editor {
+    #[default]
+    Vscode,
+    None,
+}
+
 impl From<Vendor> for String {
     fn from(value: Vendor) -> Self {
         match value {
@@ -56,42 +209,1176 @@ impl From<Vendor> for String {
     }
 }
 
+/// outcome of `create --json`
+#[derive(Serialize)]
+struct Report {
+    name: String,
+    vendor: String,
+    mcu: String,
+    target: String,
+    workspace: bool,
+    embassy_rev: Option<String>,
+    toolchain: String,
+    crates: BTreeMap<String, String>,
+    files: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
+impl Report {
+    fn new(builder: &TemplateBuilder, warnings: Vec<String>) -> Self {
+        let cfg = builder.cfg();
+        Self {
+            name: cfg.name.clone(),
+            vendor: cfg.vendor.into(),
+            mcu: cfg.mcu.clone(),
+            target: cfg.target.clone(),
+            workspace: cfg.workspace,
+            embassy_rev: cfg.rev.clone(),
+            toolchain: cfg.channel.clone(),
+            crates: cfg.versions.clone(),
+            files: [builder.files(), vec![Path::new(&cfg.name).join(LOCK_FILE)]].concat(),
+            warnings,
+        }
+    }
+}
+
 pub async fn create(cmd: CreateCommand) -> anyhow::Result<()> {
-    let name = if let Some(name) = cmd.name {
-        name
-    } else {
-        inquire::Text::new("Project name").prompt()?
-    };
+    let json = cmd.json;
+    let report = generate_project(cmd).await;
 
-    let vendor = if let Some(vendor) = cmd.vendor {
-        vendor
-    } else {
-        Vendor::from_str(
-            &inquire::Select::new("Select a vendor", DATA.vendor_list()).prompt()?,
-            true,
-        )
-        .unwrap() // should be safe
-    };
+    if json {
+        let output = match &report {
+            Ok(report) => serde_json::to_string_pretty(report)?,
+            Err(err) => serde_json::to_string_pretty(&json!({
+                "error": {
+                    "kind": ErrorKind::of(err),
+                    "message": err.to_string(),
+                }
+            }))?,
+        };
+        println!("{output}");
+    }
+
+    report.map(|_| ())
+}
+
+/// `None` when the user did not confirm the summary
+async fn generate_project(cmd: CreateCommand) -> anyhow::Result<Option<Report>> {
+    let interactive = cmd.interactive();
+    let json = cmd.json;
+    let (config, _) = cmd.resolve(true)?;
+    let builder = generate::prepare(config).await?;
+
+    if interactive && !confirm(&builder)? {
+        println!("Nothing was written");
+        return Ok(None);
+    }
+    let warnings = generate::write(&builder, Path::new("."))?;
+    if !json {
+        for warning in &warnings {
+            eprintln!("warning: {warning}");
+        }
+    }
 
-    let mcu = if let Some(mcu) = cmd.mcu {
-        mcu
-    } else {
-        inquire::Select::new("Select an MCU", DATA.mcu_list(vendor)?).prompt()?
+    Ok(Some(Report::new(&builder, warnings)))
+}
+
+/// prints what is about to be generated and asks to go ahead
+fn confirm(builder: &TemplateBuilder) -> anyhow::Result<bool> {
+    let cfg = builder.cfg();
+    let layout = match cfg.workspace {
+        true => "workspace",
+        false => "single crate",
     };
 
-    DATA.validate(vendor, &mcu)?;
+    println!();
+    println!("{}", cfg.name);
+    if let Some(board) = cfg.board.as_deref().and_then(|name| DATA.board(name)) {
+        let probe = board.probe().unwrap_or("no on-board probe");
+        println!("  {:<12} {} ({probe})", "board", board.description());
+    }
+    println!("  {:<12} {}", "vendor", String::from(cfg.vendor));
+    println!("  {:<12} {}", "mcu", cfg.mcu);
+    println!("  {:<12} {}", "target", cfg.target);
+    println!("  {:<12} {layout}", "layout");
+    println!(
+        "  {:<12} {}",
+        "embassy",
+        cfg.rev.as_deref().unwrap_or("latest, not pinned")
+    );
+    println!("  {:<12} {}", "toolchain", cfg.channel);
+    println!("  {:<12} {} at {} Hz", "time", cfg.time_driver, cfg.tick_hz);
+    println!("  {:<12} {}", "runner", name_of(cfg.runner));
+    println!("  {:<12} {}", "logging", name_of(cfg.logging));
+    println!("  {:<12} {}", "panic", name_of(cfg.panic));
+    match &cfg.executor_irq {
+        Some(irq) => println!("  {:<12} thread and interrupt on {irq}", "executor"),
+        None => println!("  {:<12} thread", "executor"),
+    }
+    if let Some(sysclk) = cfg.sysclk {
+        let source = match cfg.hse {
+            Some(hse) => format!("the {} HSE", format_freq(hse)),
+            None => "the HSI".into(),
+        };
+        println!("  {:<12} {} from {source}", "sysclk", format_freq(sysclk));
+    }
+    for peripheral in &cfg.peripherals {
+        let pins = peripheral
+            .pins
+            .iter()
+            .map(|(signal, pin)| format!("{signal} {pin}"))
+            .collect::<Vec<_>>();
+        println!("  {:<12} {peripheral} on {}", "peripheral", pins.join(", "));
+    }
+    println!("  files");
+    for file in builder.files() {
+        println!("    {}", file.display());
+    }
+    println!();
+
+    Ok(inquire::Confirm::new("Create the project?")
+        .with_default(true)
+        .prompt()?)
+}
+
+impl CreateCommand {
+    fn interactive(&self) -> bool {
+        !self.non_interactive && !self.json && std::io::stdin().is_terminal()
+    }
+
+    /// fills in the options missing from the command line from the preset, the config files
+    /// and prompts, in that order, also returns the options that were chosen rather than left
+    /// to the defaults, which is what `preset save` records
+    pub fn resolve(self, with_name: bool) -> anyhow::Result<(GeneratorConfig, Preset)> {
+        let interactive = self.interactive();
+        let preset = match &self.preset {
+            Some(preset) => Preset::load(preset)?,
+            None => Preset::default(),
+        };
+        let chosen = preset.clone().merge(self.flags());
+        let config = Config::load()?.merge(preset.config());
+        let board = match self.board.as_deref().or(preset.board.as_deref()) {
+            Some(name) => Some(DATA.board(name).ok_or_else(|| {
+                let boards = DATA.boards().iter().map(|b| b.name()).collect::<Vec<_>>();
+                ValidationError(format!(
+                    "Unknown board \"{name}\", expected one of: {}",
+                    boards.join(", ")
+                ))
+            })?),
+            None => None,
+        };
+        let query = match (board, self.mcu.or(preset.mcu.clone())) {
+            (Some(board), Some(mcu))
+                if DATA.normalize(&mcu).unwrap_or(mcu.to_lowercase()) != board.mcu() =>
+            {
+                anyhow::bail!(ValidationError(format!(
+                    "Board {} has an {}, not an {mcu}, leave out --mcu",
+                    board.name(),
+                    board.mcu()
+                )));
+            }
+            (Some(board), _) => Some(board.mcu().to_owned()),
+            (None, query) => query,
+        };
+
+        if !interactive {
+            let mut missing = vec![];
+            if with_name && self.name.is_none() {
+                missing.push("--name <NAME>");
+            }
+            if query.is_none() {
+                missing.push("--mcu <MCU> or --board <BOARD>");
+            }
+            if !missing.is_empty() {
+                anyhow::bail!(ValidationError(format!(
+                    "Missing options for a non-interactive run, pass {}",
+                    missing.join(" ")
+                )));
+            }
+        }
+        if let Some(name) = self.name.as_deref().filter(|_| with_name) {
+            validate_name(name)
+                .map_err(|e| ValidationError(format!("Invalid project name: {e}")))?;
+        }
+
+        let inferred = self
+            .vendor
+            .or_else(|| query.as_deref().and_then(|mcu| DATA.infer_vendor(mcu)));
+        let mut crates = self.crates.clone();
+        for name in &preset.crates {
+            let krate = EmbassyCrates::from_str(name, true)
+                .map_err(|_| ValidationError(format!("Unknown crate \"{name}\" in preset")))?;
+            if !crates.contains(&krate) {
+                crates.push(krate);
+            }
+        }
+
+        let executor_irq = self.executor_irq.clone().or(preset.executor_irq.clone());
+        let hse = match self.hse {
+            Some(hse) => Some(hse),
+            None => preset
+                .hse
+                .as_deref()
+                .map(parse_freq)
+                .transpose()
+                .map_err(|e| ValidationError(format!("Invalid hse in preset: {e}")))?,
+        };
+        let sysclk = match self.sysclk {
+            Some(sysclk) => Some(sysclk),
+            None => preset
+                .sysclk
+                .as_deref()
+                .map(Sysclk::parse)
+                .transpose()
+                .map_err(|e| ValidationError(format!("Invalid sysclk in preset: {e}")))?,
+        };
+
+        let mut answers = Answers {
+            name: self.name.clone().unwrap_or_default(),
+            vendor: inferred.or(config.vendor()?),
+            mcu: board.map(|b| b.mcu().to_owned()).unwrap_or_default(),
+            query,
+            board,
+            workspace: flag(self.workspace, self.no_workspace)
+                .or(config.workspace)
+                .unwrap_or_default(),
+            pin: flag(self.pin, self.no_pin).or(config.pin).unwrap_or(true),
+            runner: self.runner.unwrap_or(config.runner()?),
+            logging: self.logging.unwrap_or(config.logging()?),
+            log_level: self.log_level.unwrap_or(config.log_level()?),
+            panic: self.panic.unwrap_or(config.panic()?),
+            executor: match (self.executor, &executor_irq) {
+                (Some(executor), _) => executor,
+                (None, Some(_)) => Executor::Interrupt,
+                (None, None) => config.executor()?,
+            },
+            // the board's HSE only matters once the clocks are set up
+            hse: hse.or(board
+                .and_then(|b| b.hse())
+                .filter(|_| sysclk.is_some())
+                .map(|hse| hse.freq)),
+            sysclk,
+            editor: self.editor.unwrap_or(config.editor()?),
+            license: self.license.clone().or(config.license.clone()),
+            toolchain: self.toolchain.clone().or(config.toolchain.clone()),
+            crates,
+            asked: vec![],
+        };
+
+        if interactive {
+            // steps settled on the command line or by the preset are not asked again
+            let settled = [
+                (Step::Name, !with_name || self.name.is_some()),
+                (Step::Board, answers.query.is_some()),
+                (Step::Vendor, inferred.is_some() || preset.vendor.is_some()),
+                (
+                    Step::Layout,
+                    self.workspace || self.no_workspace || preset.workspace.is_some(),
+                ),
+                (Step::Pin, self.pin || self.no_pin || preset.pin.is_some()),
+                (
+                    Step::Toolchain,
+                    self.toolchain.is_some() || preset.toolchain.is_some(),
+                ),
+                (
+                    Step::Runner,
+                    self.runner.is_some() || preset.runner.is_some(),
+                ),
+                (
+                    Step::Logging,
+                    self.logging.is_some() || preset.logging.is_some(),
+                ),
+                (
+                    Step::LogLevel,
+                    self.log_level.is_some() || preset.log_level.is_some(),
+                ),
+                (Step::Panic, self.panic.is_some() || preset.panic.is_some()),
+                (
+                    Step::Executor,
+                    self.executor.is_some() || preset.executor.is_some() || executor_irq.is_some(),
+                ),
+                (Step::Hse, hse.is_some()),
+                (Step::Sysclk, sysclk.is_some()),
+                (
+                    Step::Editor,
+                    self.editor.is_some() || preset.editor.is_some(),
+                ),
+                (
+                    Step::License,
+                    self.license.is_some() || preset.license.is_some(),
+                ),
+                (
+                    Step::Crates,
+                    !self.crates.is_empty() || !preset.crates.is_empty(),
+                ),
+            ]
+            .into_iter()
+            .filter(|(_, settled)| *settled)
+            .map(|(step, _)| step)
+            .collect::<Vec<_>>();
+            answers.ask(&settled)?;
+        } else {
+            let Some(vendor) = answers.vendor else {
+                anyhow::bail!(ValidationError(format!(
+                    "Could not tell the vendor of \"{}\", pass --vendor <{}>",
+                    answers.query.unwrap_or_default(),
+                    DATA.vendor_list().join("|")
+                )));
+            };
+            let query = answers.query.unwrap_or_default();
+            answers.mcu = resolve_mcu(vendor, &query, false)?.unwrap_or(query);
+        }
 
-    let target = DATA.target(&mcu)?;
+        let vendor = answers.vendor.unwrap(); // set by the wizard or above
+        let mcu = answers.mcu;
+        DATA.validate(vendor, &mcu)?;
 
-    let config = GeneratorConfig {
-        name,
-        vendor,
-        mcu,
-        target,
-        no_pin: cmd.no_pin,
-        workspace: cmd.workspace,
+        let target = DATA.target(&mcu)?;
+
+        let flavor = DATA.flavor(&mcu)?;
+        let executor_irq = match (answers.executor, executor_irq) {
+            (Executor::Thread, Some(_)) => anyhow::bail!(ValidationError(
+                "--executor-irq needs the interrupt executor, pass --executor interrupt".into()
+            )),
+            (Executor::Thread, None) => None,
+            (Executor::Interrupt, irq) => {
+                let Some(irq) = irq.or(flavor.interrupts().first().cloned()) else {
+                    anyhow::bail!(ValidationError(format!(
+                        "No free interrupt is known for {mcu}, pass --executor-irq <IRQ>"
+                    )));
+                };
+                if !flavor.interrupts().contains(&irq) {
+                    anyhow::bail!(ValidationError(format!(
+                        "Interrupt {irq} is not free on {mcu}, expected one of: {}",
+                        flavor.interrupts().join(", ")
+                    )));
+                }
+                Some(irq)
+            }
+        };
+        let task_arena_size = self.task_arena_size.or(preset.task_arena_size);
+        if let Some(size) = task_arena_size {
+            DATA.validate_task_arena_size(&mcu, size)
+                .map_err(|e| ValidationError(format!("Invalid task arena size: {e}")))?;
+        }
+
+        let sysclk = match (answers.hse, answers.sysclk) {
+            (None, None) => None,
+            (hse, sysclk) => {
+                if vendor != Vendor::St {
+                    anyhow::bail!(ValidationError(
+                        "--hse and --sysclk only apply to STM32 MCUs".into()
+                    ));
+                }
+                let Some(clocks) = DATA.clocks(&mcu) else {
+                    anyhow::bail!(ValidationError(format!(
+                        "Setting up the clocks of {mcu} is not supported yet, \
+                         leave out --hse and --sysclk"
+                    )));
+                };
+                let plan = clocks
+                    .plan(hse, sysclk.unwrap_or(Sysclk::Max))
+                    .map_err(|e| ValidationError(format!("Invalid clock configuration: {e}")))?;
+                Some(plan.sysclk())
+            }
+        };
+
+        let wanted = match self.with_peripheral.is_empty() {
+            true => preset.peripherals,
+            false => self.with_peripheral,
+        };
+        let peripherals = match (
+            wanted.is_empty(),
+            self.chip_data.or(config.chip_data.clone()),
+        ) {
+            (true, _) => vec![],
+            (false, Some(chip_data)) => peripherals::resolve_all(
+                &chip_data,
+                vendor,
+                &mcu,
+                answers.board,
+                &[],
+                &wanted,
+                executor_irq.as_deref(),
+            )?,
+            (false, None) => anyhow::bail!(ValidationError(
+                "Peripherals are checked against the chip data, pass --chip-data <PATH> or set \
+                 it with `embassy-cli config set chip-data <PATH>`"
+                    .into()
+            )),
+        };
+
+        let time = DATA.time(&mcu)?;
+        let time_driver = match self.time_driver.or(preset.time_driver) {
+            Some(driver) => {
+                let driver = match driver.starts_with("time-driver") {
+                    true => driver,
+                    false => format!("time-driver-{driver}"),
+                };
+                if !time.drivers().contains(&driver) {
+                    anyhow::bail!(ValidationError(format!(
+                        "Invalid time driver \"{driver}\" for {mcu}, expected one of: {}",
+                        time.drivers().join(", ")
+                    )));
+                }
+                driver
+            }
+            None => time.default_driver().to_owned(),
+        };
+        let tick_hz = self.tick_hz.or(preset.tick_hz).unwrap_or(time.tick_hz());
+        time.validate_tick_hz(&mcu, tick_hz)
+            .map_err(|e| ValidationError(format!("Invalid tick rate: {e}")))?;
+
+        let mut features = vec![];
+        for feature in preset.features.into_iter().chain(self.features) {
+            let feature = match feature.starts_with("embassy-") {
+                true => feature,
+                false => format!("embassy-{feature}"),
+            };
+            if !features.contains(&feature) {
+                features.push(feature);
+            }
+        }
+
+        let config = GeneratorConfig {
+            name: answers.name,
+            vendor,
+            mcu,
+            board: answers.board.map(|b| b.name().to_owned()),
+            target,
+            no_pin: !answers.pin,
+            workspace: answers.workspace,
+            runner: answers.runner,
+            logging: answers.logging,
+            log_level: answers.log_level,
+            panic: answers.panic,
+            editor: answers.editor,
+            upstream: self.upstream.or(config.upstream).unwrap_or_default(),
+            license: answers.license,
+            crates: answers.crates,
+            features,
+            template: self.template.or(preset.template),
+            time_driver,
+            tick_hz,
+            arch: flavor.arch().to_owned(),
+            executor: answers.executor,
+            executor_irq,
+            task_arena_size,
+            hse: answers.hse,
+            sysclk,
+            peripherals,
+            tasks: preset.tasks,
+            toolchain: answers.toolchain,
+            rev: None,
+            channel: String::new(),
+            versions: BTreeMap::new(),
+        };
+
+        let crate_names = project_crates(&config)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        for feature in &config.features {
+            match feature.split_once('/') {
+                Some((krate, _)) if crate_names.iter().any(|n| n == krate) => {}
+                _ => anyhow::bail!(ValidationError(format!(
+                    "Invalid feature \"{feature}\", expected <crate>/<feature> for one of: {}",
+                    crate_names.join(", ")
+                ))),
+            }
+        }
+        if let Some(template) = config.template.as_ref().filter(|t| !t.is_dir()) {
+            anyhow::bail!(ValidationError(format!(
+                "Template {} is not a directory",
+                template.display()
+            )));
+        }
+
+        let chosen = answered(chosen, &answers.asked, &config, answers.sysclk);
+        Ok((config, chosen))
+    }
+
+    /// the options given on the command line, as a preset
+    fn flags(&self) -> Preset {
+        Preset {
+            vendor: self.vendor.map(String::from),
+            mcu: self.mcu.clone(),
+            board: self.board.clone(),
+            runner: self.runner.map(name_of),
+            workspace: flag(self.workspace, self.no_workspace),
+            pin: flag(self.pin, self.no_pin),
+            upstream: self.upstream.clone(),
+            logging: self.logging.map(name_of),
+            log_level: self.log_level.map(name_of),
+            panic: self.panic.map(name_of),
+            executor: self.executor.map(name_of),
+            editor: self.editor.map(name_of),
+            license: self.license.clone(),
+            toolchain: self.toolchain.clone(),
+            crates: self.crates.iter().map(|c| c.name().to_owned()).collect(),
+            features: self.features.clone(),
+            template: self.template.clone(),
+            time_driver: self.time_driver.clone(),
+            tick_hz: self.tick_hz,
+            executor_irq: self.executor_irq.clone(),
+            task_arena_size: self.task_arena_size,
+            hse: self.hse.map(format_freq),
+            sysclk: self.sysclk.map(sysclk_name),
+            peripherals: self.with_peripheral.clone(),
+            tasks: vec![],
+        }
+    }
+}
+
+/// `chosen` with the answers to the questions that were asked, and the values it already has
+/// as they were resolved, eg: the full MCU name for a query
+fn answered(
+    mut chosen: Preset,
+    asked: &[Step],
+    cfg: &GeneratorConfig,
+    sysclk: Option<Sysclk>,
+) -> Preset {
+    let asked = |step| asked.contains(&step);
+    if chosen.vendor.is_some() || asked(Step::Vendor) {
+        chosen.vendor = Some(cfg.vendor.into());
+    }
+    if chosen.mcu.is_some() || asked(Step::Mcu) {
+        chosen.mcu = Some(cfg.mcu.clone());
+    }
+    if asked(Step::Board) {
+        chosen.board = cfg.board.clone();
+    }
+    if asked(Step::Layout) {
+        chosen.workspace = Some(cfg.workspace);
+    }
+    if asked(Step::Pin) {
+        chosen.pin = Some(!cfg.no_pin);
+    }
+    if asked(Step::Toolchain) {
+        chosen.toolchain = cfg.toolchain.clone();
+    }
+    if asked(Step::Runner) {
+        chosen.runner = Some(name_of(cfg.runner));
+    }
+    if asked(Step::Logging) {
+        chosen.logging = Some(name_of(cfg.logging));
+    }
+    if asked(Step::LogLevel) {
+        chosen.log_level = Some(name_of(cfg.log_level));
+    }
+    if asked(Step::Panic) {
+        chosen.panic = Some(name_of(cfg.panic));
+    }
+    if asked(Step::Executor) {
+        chosen.executor = Some(name_of(cfg.executor));
+    }
+    if chosen.hse.is_some() || asked(Step::Hse) {
+        chosen.hse = cfg.hse.map(format_freq);
+    }
+    if chosen.sysclk.is_some() || asked(Step::Sysclk) {
+        chosen.sysclk = sysclk.map(sysclk_name);
+    }
+    if asked(Step::Editor) {
+        chosen.editor = Some(name_of(cfg.editor));
+    }
+    if asked(Step::License) {
+        chosen.license = cfg.license.clone();
+    }
+    if !chosen.crates.is_empty() || asked(Step::Crates) {
+        chosen.crates = cfg.crates.iter().map(|c| c.name().to_owned()).collect();
+    }
+    if chosen.time_driver.is_some() {
+        chosen.time_driver = Some(cfg.time_driver.clone());
+    }
+    if !chosen.peripherals.is_empty() {
+        chosen.peripherals = cfg.peripherals.clone();
+    }
+    chosen
+}
+
+/// "max" or the frequency, the way `--sysclk` reads it
+fn sysclk_name(sysclk: Sysclk) -> String {
+    match sysclk {
+        Sysclk::Max => "max".into(),
+        Sysclk::Hz(hz) => format_freq(hz),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Step {
+    Name,
+    Board,
+    Vendor,
+    Mcu,
+    Layout,
+    Pin,
+    Toolchain,
+    Runner,
+    Logging,
+    LogLevel,
+    Panic,
+    Executor,
+    Hse,
+    Sysclk,
+    Editor,
+    License,
+    Crates,
+}
+
+const STEPS: [Step; 17] = [
+    Step::Name,
+    Step::Board,
+    Step::Vendor,
+    Step::Mcu,
+    Step::Layout,
+    Step::Pin,
+    Step::Toolchain,
+    Step::Runner,
+    Step::Logging,
+    Step::LogLevel,
+    Step::Panic,
+    Step::Executor,
+    Step::Hse,
+    Step::Sysclk,
+    Step::Editor,
+    Step::License,
+    Step::Crates,
+];
+
+/// options of the new project, pre-filled from the command line, the preset and the config files
+struct Answers {
+    name: String,
+    vendor: Option<Vendor>,
+    /// MCU as given on the command line or in the preset
+    query: Option<String>,
+    mcu: String,
+    board: Option<&'static Board>,
+    workspace: bool,
+    pin: bool,
+    runner: Runner,
+    logging: Logging,
+    log_level: LogLevel,
+    panic: Panic,
+    executor: Executor,
+    /// HSE crystal in Hz
+    hse: Option<u32>,
+    sysclk: Option<Sysclk>,
+    editor: Editor,
+    license: Option<String>,
+    toolchain: Option<String>,
+    crates: Vec<EmbassyCrates>,
+    /// steps the user answered, in the wizard
+    asked: Vec<Step>,
+}
+
+impl Answers {
+    /// asks every step that is not settled, escape goes back to the previous question
+    fn ask(&mut self, settled: &[Step]) -> anyhow::Result<()> {
+        let steps = STEPS
+            .into_iter()
+            .filter(|step| !settled.contains(step))
+            .collect::<Vec<_>>();
+
+        let mut i = 0;
+        let mut back = false;
+        while i < steps.len() {
+            // going back skips steps without a prompt to stop at
+            if !self.applies(steps[i]) || (back && self.resolved(steps[i])) {
+                back &= i > 0;
+                match back {
+                    true => i -= 1,
+                    false => i += 1,
+                }
+                continue;
+            }
+
+            back = self.ask_step(steps[i])?.is_none();
+            if !back && !self.asked.contains(&steps[i]) {
+                self.asked.push(steps[i]);
+            }
+            match back {
+                true => i = i.saturating_sub(1),
+                false => i += 1,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// whether a step makes sense given the earlier answers
+    fn applies(&self, step: Step) -> bool {
+        match step {
+            Step::Vendor | Step::Mcu => self.board.is_none(),
+            Step::LogLevel => self.logging != Logging::None,
+            Step::Hse | Step::Sysclk => {
+                self.vendor == Some(Vendor::St) && DATA.clocks(&self.mcu).is_some()
+            }
+            _ => true,
+        }
+    }
+
+    /// whether a step answers itself, like an MCU query that matches a single part
+    fn resolved(&self, step: Step) -> bool {
+        match (step, self.vendor, &self.query) {
+            (Step::Mcu, Some(vendor), Some(query)) => DATA.search(Some(vendor), query).len() < 2,
+            _ => false,
+        }
+    }
+
+    /// `None` when the user went back
+    fn ask_step(&mut self, step: Step) -> anyhow::Result<Option<()>> {
+        let help = "esc to go back";
+        match step {
+            Step::Name => {
+                let name = inquire::Text::new("Project name")
+                    .with_initial_value(&self.name)
+                    .with_validator(|name: &str| {
+                        Ok(match validate_name(name) {
+                            Ok(()) => Validation::Valid,
+                            Err(e) => Validation::Invalid(e.into()),
+                        })
+                    })
+                    .prompt();
+                let Some(name) = answer(name)? else {
+                    return Ok(None);
+                };
+                self.name = name;
+            }
+            Step::Board => {
+                let boards = DATA.boards();
+                let mut options = vec!["none, pick an MCU".to_owned()];
+                options.extend(
+                    boards
+                        .iter()
+                        .map(|b| format!("{:<16} {}", b.name(), b.description())),
+                );
+                let cursor = self
+                    .board
+                    .and_then(|board| boards.iter().position(|b| b.name() == board.name()))
+                    .map_or(0, |i| i + 1);
+                let board = inquire::Select::new("Board", options)
+                    .with_starting_cursor(cursor)
+                    .with_help_message(help)
+                    .raw_prompt();
+                let Some(board) = answer(board)? else {
+                    return Ok(None);
+                };
+                self.board = board.index.checked_sub(1).map(|i| &boards[i]);
+                if let Some(board) = self.board {
+                    self.vendor = DATA.vendor_of(board.mcu());
+                    self.mcu = board.mcu().to_owned();
+                }
+            }
+            Step::Vendor => {
+                let vendors = DATA.vendor_list();
+                let cursor = self
+                    .vendor
+                    .and_then(|v| vendors.iter().position(|name| *name == String::from(v)))
+                    .unwrap_or(0);
+                let vendor = inquire::Select::new("Select a vendor", vendors)
+                    .with_starting_cursor(cursor)
+                    .with_help_message(help)
+                    .prompt();
+                let Some(vendor) = answer(vendor)? else {
+                    return Ok(None);
+                };
+                let vendor = Some(Vendor::from_str(&vendor, true).unwrap()); // should be safe
+                if vendor != self.vendor {
+                    self.mcu.clear();
+                }
+                self.vendor = vendor;
+            }
+            Step::Mcu => {
+                let vendor = self.vendor.unwrap(); // asked in an earlier step
+
+                // checked here rather than at the end, so another vendor asks for another MCU
+                loop {
+                    let mcu = match &self.query {
+                        Some(query) => resolve_mcu(vendor, query, true)?,
+                        None => prompt_mcu(vendor)?,
+                    };
+                    let Some(mcu) = mcu else {
+                        return Ok(None);
+                    };
+                    match DATA.validate(vendor, &mcu) {
+                        Ok(()) => {
+                            self.mcu = mcu;
+                            break;
+                        }
+                        Err(e) => {
+                            match e.downcast_ref::<DataError>() {
+                                Some(e) => eprintln!("{}", e.render()),
+                                None => eprintln!("error: {e}"),
+                            }
+                            self.query = None;
+                        }
+                    }
+                }
+            }
+            Step::Layout => {
+                let layouts = vec!["single crate", "workspace"];
+                let layout = inquire::Select::new("Project layout", layouts)
+                    .with_starting_cursor(self.workspace as usize)
+                    .with_help_message(help)
+                    .prompt();
+                let Some(layout) = answer(layout)? else {
+                    return Ok(None);
+                };
+                self.workspace = layout == "workspace";
+            }
+            Step::Pin => {
+                let pin = inquire::Confirm::new("Pin embassy to its latest commit?")
+                    .with_default(self.pin)
+                    .with_help_message(help)
+                    .prompt();
+                let Some(pin) = answer(pin)? else {
+                    return Ok(None);
+                };
+                self.pin = pin;
+            }
+            Step::Toolchain => {
+                const OTHER: &str = "another channel";
+                let current = self.toolchain.clone().unwrap_or("default".into());
+                let mut options = vec!["default".to_owned(), "stable".into(), "nightly".into()];
+                if !options.contains(&current) {
+                    options.push(current.clone());
+                }
+                options.push(OTHER.into());
+                // esc on the channel goes back to the list
+                let toolchain = loop {
+                    let toolchain = inquire::Select::new("Toolchain", options.clone())
+                        .with_starting_cursor(
+                            options.iter().position(|o| *o == current).unwrap_or(0),
+                        )
+                        .with_help_message(
+                            "default is stable when embassy supports it, esc to go back",
+                        )
+                        .prompt();
+                    let Some(toolchain) = answer(toolchain)? else {
+                        return Ok(None);
+                    };
+                    if toolchain != OTHER {
+                        break toolchain;
+                    }
+                    let channel = inquire::Text::new("Toolchain channel")
+                        .with_validator(|channel: &str| {
+                            Ok(match channel.trim().is_empty() {
+                                true => Validation::Invalid("enter a channel".into()),
+                                false => Validation::Valid,
+                            })
+                        })
+                        .with_help_message("eg: nightly-2024-04-14 or 1.78, esc to go back")
+                        .prompt();
+                    if let Some(channel) = answer(channel)? {
+                        break channel.trim().to_owned();
+                    }
+                };
+                self.toolchain = Some(toolchain).filter(|t| t != "default");
+            }
+            Step::Runner => {
+                let Some(runner) = select_enum("Runner", self.runner)? else {
+                    return Ok(None);
+                };
+                self.runner = runner;
+            }
+            Step::Logging => {
+                let Some(logging) = select_enum("Logging backend", self.logging)? else {
+                    return Ok(None);
+                };
+                self.logging = logging;
+            }
+            Step::LogLevel => {
+                let Some(log_level) = select_enum("Log level", self.log_level)? else {
+                    return Ok(None);
+                };
+                self.log_level = log_level;
+            }
+            Step::Panic => {
+                let Some(panic) = select_enum("Panic handler", self.panic)? else {
+                    return Ok(None);
+                };
+                self.panic = panic;
+            }
+            Step::Executor => {
+                let Some(executor) = select_enum("Executor", self.executor)? else {
+                    return Ok(None);
+                };
+                self.executor = executor;
+            }
+            Step::Hse => {
+                let initial = self
+                    .hse
+                    .or(self.board.and_then(|b| b.hse()).map(|hse| hse.freq));
+                let hse = inquire::Text::new("HSE crystal frequency")
+                    .with_initial_value(&initial.map(format_freq).unwrap_or_default())
+                    .with_validator(|hse: &str| {
+                        Ok(match hse.trim().is_empty() {
+                            true => Validation::Valid,
+                            false => match parse_freq(hse) {
+                                Ok(_) => Validation::Valid,
+                                Err(e) => Validation::Invalid(e.into()),
+                            },
+                        })
+                    })
+                    .with_help_message("eg: 8MHz, leave empty to run from the HSI, esc to go back")
+                    .prompt();
+                let Some(hse) = answer(hse)? else {
+                    return Ok(None);
+                };
+                self.hse = match hse.trim().is_empty() {
+                    true => None,
+                    false => Some(parse_freq(&hse).unwrap()), // should be safe
+                };
+            }
+            Step::Sysclk => {
+                let clocks = DATA.clocks(&self.mcu).unwrap(); // checked by applies
+                let hse = self.hse;
+                let initial = match self.sysclk {
+                    Some(Sysclk::Max) => "max".to_owned(),
+                    Some(Sysclk::Hz(hz)) => format_freq(hz),
+                    None => String::new(),
+                };
+                let empty = match hse {
+                    Some(_) => "max",
+                    None => "the HAL defaults",
+                };
+                let sysclk = inquire::Text::new("System clock")
+                    .with_initial_value(&initial)
+                    .with_validator(move |sysclk: &str| {
+                        if sysclk.trim().is_empty() {
+                            return Ok(Validation::Valid);
+                        }
+                        Ok(
+                            match Sysclk::parse(sysclk).and_then(|s| clocks.plan(hse, s)) {
+                                Ok(_) => Validation::Valid,
+                                Err(e) => Validation::Invalid(e.into()),
+                            },
+                        )
+                    })
+                    .with_help_message(&format!(
+                        "max or up to {}, leave empty for {empty}, esc to go back",
+                        format_freq(clocks.sysclk_max())
+                    ))
+                    .prompt();
+                let Some(sysclk) = answer(sysclk)? else {
+                    return Ok(None);
+                };
+                self.sysclk = match sysclk.trim().is_empty() {
+                    true => None,
+                    false => Some(Sysclk::parse(&sysclk).unwrap()), // should be safe
+                };
+            }
+            Step::Editor => {
+                let Some(editor) = select_enum("Editor settings", self.editor)? else {
+                    return Ok(None);
+                };
+                self.editor = editor;
+            }
+            Step::License => {
+                let license = inquire::Text::new("License")
+                    .with_initial_value(self.license.as_deref().unwrap_or_default())
+                    .with_help_message(
+                        "SPDX expression, eg: MIT OR Apache-2.0, leave empty for none",
+                    )
+                    .prompt();
+                let Some(license) = answer(license)? else {
+                    return Ok(None);
+                };
+                self.license = Some(license.trim().to_owned()).filter(|l| !l.is_empty());
+            }
+            Step::Crates => {
+                let vendor = self.vendor.unwrap(); // asked in an earlier step
+                let included =
+                    crate_features(vendor, &self.mcu, &CrateOptions::defaults(&self.mcu)?)
+                        .into_iter()
+                        .map(|(name, _)| name)
+                        .collect::<Vec<_>>();
+                let hals = [EmbassyCrates::Nrf, EmbassyCrates::Rp, EmbassyCrates::Stm32];
+                let options = EmbassyCrates::value_variants()
+                    .iter()
+                    .filter(|c| !hals.contains(c))
+                    .filter(|c| !included.iter().any(|name| name == c.name()))
+                    .filter(|c| **c != EmbassyCrates::Stm32Wpan || vendor == Vendor::St)
+                    .map(|c| c.name())
+                    .collect::<Vec<_>>();
+                if options.is_empty() {
+                    return Ok(Some(()));
+                }
+
+                let selected = options
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, name)| self.crates.iter().any(|c| c.name() == **name))
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>();
+                let crates = inquire::MultiSelect::new("Extra embassy crates", options)
+                    .with_default(&selected)
+                    .with_help_message("space to select, enter to confirm, esc to go back")
+                    .prompt();
+                let Some(crates) = answer(crates)? else {
+                    return Ok(None);
+                };
+                self.crates = crates
+                    .into_iter()
+                    .map(|name| EmbassyCrates::from_str(name, true).unwrap()) // should be safe
+                    .collect();
+            }
+        }
+
+        Ok(Some(()))
+    }
+}
+
+/// cargo's rules for package names, and the project directory must not exist yet
+fn validate_name(name: &str) -> Result<(), String> {
+    const KEYWORDS: [&str; 38] = [
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ];
+    const RESERVED: [&str; 6] = ["test", "core", "std", "alloc", "proc_macro", "build"];
+
+    let Some(first) = name.chars().next() else {
+        return Err("the name cannot be empty".into());
     };
-    generate::create(config).await?;
+    if first.is_ascii_digit() {
+        return Err("the name cannot start with a digit".into());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
+    {
+        return Err(format!(
+            "invalid character '{c}', only letters, digits, - and _ are allowed"
+        ));
+    }
+    if KEYWORDS.contains(&name) {
+        return Err(format!("\"{name}\" is a Rust keyword"));
+    }
+    if RESERVED.contains(&name) {
+        return Err(format!(
+            "\"{name}\" conflicts with a built-in crate or directory"
+        ));
+    }
+    if Path::new(name).exists() {
+        return Err(format!("{name} already exists in the current directory"));
+    }
 
     Ok(())
 }
+
+/// `None` when the prompt was left with escape
+fn answer<T>(result: inquire::error::InquireResult<T>) -> anyhow::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn select_enum<T: ValueEnum + Copy + PartialEq>(
+    message: &str,
+    current: T,
+) -> anyhow::Result<Option<T>> {
+    let names = T::value_variants()
+        .iter()
+        .map(|v| name_of(*v))
+        .collect::<Vec<_>>();
+    let cursor = T::value_variants()
+        .iter()
+        .position(|v| *v == current)
+        .unwrap_or(0);
+
+    let name = inquire::Select::new(message, names)
+        .with_starting_cursor(cursor)
+        .with_help_message("esc to go back")
+        .prompt();
+    Ok(answer(name)?.map(|name| T::from_str(&name, true).unwrap())) // should be safe
+}
+
+/// value of a `--flag`/`--no-flag` pair, `None` when neither was given
+fn flag(yes: bool, no: bool) -> Option<bool> {
+    match (yes, no) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// resolves partial or marketing part numbers, asking the user to pick when several match,
+/// `None` when the user went back
+fn resolve_mcu(vendor: Vendor, mcu: &str, interactive: bool) -> anyhow::Result<Option<String>> {
+    let mut matches = DATA.search(Some(vendor), mcu);
+    match matches.len() {
+        0 => Ok(Some(mcu.to_owned())),
+        1 => Ok(Some(matches.remove(0))),
+        _ if !interactive => anyhow::bail!(ValidationError(format!(
+            "Several MCUs match \"{mcu}\", pass one of them with --mcu: {}",
+            matches.join(", ")
+        ))),
+        _ => {
+            answer(inquire::Select::new(&format!("Several MCUs match \"{mcu}\""), matches).prompt())
+        }
+    }
+}
+
+/// asks for a part number, or lets the user browse by family and line when left empty,
+/// `None` when the user went back
+fn prompt_mcu(vendor: Vendor) -> anyhow::Result<Option<String>> {
+    loop {
+        let query = inquire::Text::new("Search an MCU")
+            .with_help_message("eg: f446re or STM32F446RET6, leave empty to browse, esc to go back")
+            .prompt();
+        let Some(query) = answer(query)? else {
+            return Ok(None);
+        };
+        if !query.trim().is_empty() {
+            match resolve_mcu(vendor, &query, true)? {
+                Some(mcu) => return Ok(Some(mcu)),
+                None => continue,
+            }
+        }
+
+        let mcus = DATA.mcu_list(vendor)?;
+        let families = unique(mcus.iter().map(|m| DATA.family(m).unwrap_or_default()));
+        let Some(family) = select_level("Select a family", families)? else {
+            continue;
+        };
+
+        let mcus = mcus
+            .into_iter()
+            .filter(|m| DATA.family(m).unwrap_or_default() == family)
+            .collect::<Vec<_>>();
+        let lines = unique(mcus.iter().map(|m| DATA.line(m)));
+        let Some(line) = select_level("Select a line", lines)? else {
+            continue;
+        };
+
+        let parts = mcus
+            .into_iter()
+            .filter(|m| DATA.line(m) == line)
+            .collect::<Vec<_>>();
+        if let Some(mcu) = select_level("Select an MCU", parts)? {
+            return Ok(Some(mcu));
+        }
+    }
+}
+
+fn select_level(message: &str, mut options: Vec<String>) -> anyhow::Result<Option<String>> {
+    if options.len() == 1 {
+        return Ok(Some(options.remove(0)));
+    }
+
+    answer(inquire::Select::new(message, options).prompt())
+}
+
+fn unique(items: impl Iterator<Item = String>) -> Vec<String> {
+    let mut unique: Vec<String> = vec![];
+    for item in items {
+        if !unique.contains(&item) {
+            unique.push(item);
+        }
+    }
+    unique
+}
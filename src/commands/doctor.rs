@@ -0,0 +1,333 @@
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use serde::Serialize;
+
+use crate::project::{read_manifest, Project};
+
+#[derive(Parser)]
+pub struct DoctorCommand {
+    /// Print the results as JSON
+    #[clap(long)]
+    json: bool,
+    /// Path to the project
+    #[clap(short, long, default_value = ".")]
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Check {
+    name: String,
+    status: Status,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn failed(
+        status: Status,
+        name: impl Into<String>,
+        message: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// probe vendor IDs matched against installed udev rules
+const PROBE_VENDOR_IDS: [(&str, &str); 4] = [
+    ("0483", "ST-Link"),
+    ("1366", "J-Link"),
+    ("2e8a", "Raspberry Pi Debug Probe"),
+    ("0d28", "DAPLink"),
+];
+
+pub fn doctor(cmd: DoctorCommand) -> anyhow::Result<()> {
+    let project = Project::discover(&cmd.path).ok();
+    let mut checks = vec![];
+
+    let channel = project
+        .as_ref()
+        .and_then(|p| toolchain_field(&p.root, "channel").into_iter().next());
+    let rustup = run("rustup", &["--version"]).is_some();
+    checks.push(match rustup {
+        true => Check::ok("rustup", "rustup is installed"),
+        false => Check::failed(
+            Status::Error,
+            "rustup",
+            "rustup is not installed",
+            "install rustup from https://rustup.rs",
+        ),
+    });
+
+    if let Some(project) = &project {
+        if let (Some(channel), true) = (&channel, rustup) {
+            checks.extend(check_toolchain(&project.root, channel));
+        }
+        checks.extend(check_runners(&project.root));
+    }
+
+    // the toolchain of the project is the one that formats it
+    let toolchain = channel.as_ref().filter(|_| rustup);
+    let mut args = toolchain
+        .map(|c| format!("+{c}"))
+        .into_iter()
+        .collect::<Vec<_>>();
+    args.extend(["fmt".into(), "--version".into()]);
+    let dir = project
+        .as_ref()
+        .map_or(cmd.path.as_path(), |p| p.root.as_path());
+    checks.push(match run_in(dir, "cargo", &args) {
+        Some(_) => Check::ok("cargo fmt", "cargo fmt is available"),
+        None => Check::failed(
+            Status::Warning,
+            "cargo fmt",
+            "cargo fmt is not available, generated projects will not be formatted",
+            match toolchain {
+                Some(channel) => format!("rustup component add rustfmt --toolchain {channel}"),
+                None => "rustup component add rustfmt".into(),
+            },
+        ),
+    });
+
+    if cfg!(target_os = "linux") {
+        checks.push(check_udev_rules());
+    }
+
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        for check in &checks {
+            let icon = match check.status {
+                Status::Ok => "✓",
+                Status::Warning => "!",
+                Status::Error => "✗",
+            };
+            println!("{icon} {}: {}", check.name, check.message);
+            if let Some(fix) = &check.fix {
+                println!("    fix: {fix}");
+            }
+        }
+    }
+
+    let errors = checks.iter().filter(|c| c.status == Status::Error).count();
+    if errors > 0 {
+        anyhow::bail!("{errors} check(s) failed");
+    }
+
+    Ok(())
+}
+
+fn check_toolchain(root: &Path, channel: &str) -> Vec<Check> {
+    let mut checks = vec![];
+
+    // toolchains are listed by their full name, eg: "nightly-2024-06-01-x86_64-unknown-linux-gnu"
+    let host = host();
+    let installed = run("rustup", &["toolchain", "list"]).unwrap_or_default();
+    let installed = installed
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .any(|name| {
+            name == channel
+                || host.as_deref().is_some_and(|host| {
+                    name.strip_suffix(host).and_then(|n| n.strip_suffix('-')) == Some(channel)
+                })
+        });
+    if !installed {
+        checks.push(Check::failed(
+            Status::Error,
+            "toolchain",
+            format!("toolchain {channel} from rust-toolchain.toml is not installed"),
+            format!("rustup toolchain install {channel}"),
+        ));
+        return checks;
+    }
+    checks.push(Check::ok(
+        "toolchain",
+        format!("toolchain {channel} is installed"),
+    ));
+
+    let toolchain = format!("+{channel}");
+    let mut targets = toolchain_field(root, "targets");
+    targets.extend(build_target(root));
+    targets.sort();
+    targets.dedup();
+    let installed =
+        run("rustup", &[&toolchain, "target", "list", "--installed"]).unwrap_or_default();
+    for target in targets {
+        checks.push(match installed.lines().any(|l| l == target) {
+            true => Check::ok("target", format!("{target} is installed")),
+            false => Check::failed(
+                Status::Error,
+                "target",
+                format!("{target} is not installed for {channel}"),
+                format!("rustup target add {target} --toolchain {channel}"),
+            ),
+        });
+    }
+
+    let installed =
+        run("rustup", &[&toolchain, "component", "list", "--installed"]).unwrap_or_default();
+    for component in toolchain_field(root, "components") {
+        checks.push(match installed.lines().any(|l| l.starts_with(&component)) {
+            true => Check::ok("component", format!("{component} is installed")),
+            false => Check::failed(
+                Status::Error,
+                "component",
+                format!("{component} is not installed for {channel}"),
+                format!("rustup component add {component} --toolchain {channel}"),
+            ),
+        });
+    }
+
+    checks
+}
+
+fn check_runners(root: &Path) -> Vec<Check> {
+    let Ok(config) = read_manifest(&root.join(".cargo/config.toml")) else {
+        return vec![];
+    };
+
+    let mut binaries = vec![];
+    let targets = config.get("target").and_then(|t| t.as_table_like());
+    for (_, target) in targets.into_iter().flat_map(|t| t.iter()) {
+        if let Some(runner) = target.get("runner").and_then(|r| r.as_str()) {
+            binaries.extend(runner.split_whitespace().next().map(String::from));
+        }
+        let rustflags = target.get("rustflags").and_then(|f| f.as_array());
+        let flip_link = target.get("linker").and_then(|l| l.as_str()) == Some("flip-link")
+            || rustflags
+                .into_iter()
+                .flatten()
+                .any(|f| f.as_str() == Some("linker=flip-link"));
+        if flip_link {
+            binaries.push("flip-link".into());
+        }
+    }
+
+    binaries
+        .into_iter()
+        .map(|binary| match find_in_path(&binary) {
+            Some(path) => Check::ok("runner", format!("{binary} found at {}", path.display())),
+            None => {
+                let package = match binary.as_str() {
+                    "probe-rs" => "probe-rs-tools",
+                    other => other,
+                };
+                Check::failed(
+                    Status::Error,
+                    "runner",
+                    format!("{binary} from .cargo/config.toml is not installed"),
+                    format!("cargo install {package} --locked"),
+                )
+            }
+        })
+        .collect()
+}
+
+fn check_udev_rules() -> Check {
+    let rules = ["/etc/udev/rules.d", "/lib/udev/rules.d"]
+        .into_iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .to_lowercase();
+
+    let missing = PROBE_VENDOR_IDS
+        .iter()
+        .filter(|(id, _)| !rules.contains(&format!("\"{id}\"")))
+        .map(|(_, probe)| *probe)
+        .collect::<Vec<_>>();
+
+    match missing.is_empty() {
+        true => Check::ok("udev", "udev rules for common probes are installed"),
+        false => Check::failed(
+            Status::Warning,
+            "udev",
+            format!("no udev rules found for {}", missing.join(", ")),
+            "install the probe-rs udev rules: https://probe.rs/docs/getting-started/probe-setup/",
+        ),
+    }
+}
+
+/// reads a string or list of strings from the `[toolchain]` table of `rust-toolchain.toml`
+fn toolchain_field(root: &Path, field: &str) -> Vec<String> {
+    let Ok(toolchain) = read_manifest(&root.join("rust-toolchain.toml")) else {
+        return vec![];
+    };
+    let Some(value) = toolchain.get("toolchain").and_then(|t| t.get(field)) else {
+        return vec![];
+    };
+
+    match value.as_array() {
+        Some(values) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(String::from)
+            .collect(),
+        None => value.as_str().map(String::from).into_iter().collect(),
+    }
+}
+
+fn build_target(root: &Path) -> Option<String> {
+    let config = read_manifest(&root.join(".cargo/config.toml")).ok()?;
+    let target = config.get("build")?.get("target")?.as_str()?;
+    Some(target.to_owned())
+}
+
+/// host triple rustup installs toolchains for
+fn host() -> Option<String> {
+    // outside of the project, so its rust-toolchain.toml is not read
+    let show = run_in(&std::env::temp_dir(), "rustup", &["show"])?;
+    let host = show.lines().find_map(|l| l.strip_prefix("Default host:"))?;
+    Some(host.trim().to_owned())
+}
+
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    run_in(Path::new("."), program, args)
+}
+
+fn run_in<S: AsRef<std::ffi::OsStr>>(dir: &Path, program: &str, args: &[S]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(binary))
+        .find(|path| path.is_file() || path.with_extension("exe").is_file())
+}
@@ -0,0 +1,401 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use toml_edit::{Array, InlineTable};
+
+use crate::{
+    commands::create::Logging,
+    config::Config,
+    error::ValidationError,
+    generator::{
+        data::DATA,
+        drivers::{Bus, Driver},
+        generate,
+        lock::Lock,
+        peripherals::{self, Peripheral},
+        tasks::{self, Input, Task},
+        templates,
+    },
+    project::{
+        dependency_tables, read_manifest, table_mut, upsert_dependency, write_manifest, Project,
+    },
+};
+
+#[derive(Parser)]
+pub struct GenerateCommand {
+    #[command(subcommand)]
+    what: Generate,
+    /// Path to the project
+    #[clap(short, long, default_value = ".", global = true)]
+    path: PathBuf,
+}
+
+#[derive(Subcommand)]
+enum Generate {
+    /// Add a UART, I2C or SPI driver module with its interrupt bindings and an example task
+    Peripheral {
+        /// kind:INSTANCE with optional pins and DMA channels, eg: i2c:I2C1:scl=PB8,sda=PB9
+        #[clap(value_name = "SPEC", value_parser = Peripheral::parse)]
+        spec: Peripheral,
+        /// Chip description in the stm32-data format, or a directory of them (default: from the
+        /// config files)
+        #[clap(long, value_name = "PATH")]
+        chip_data: Option<PathBuf>,
+    },
+    /// Add a module with an embassy task and spawn it from main.rs
+    Task {
+        /// Name of the module, eg: blinker
+        name: String,
+        /// Instances of the task that can run at once
+        #[clap(long, value_name = "N")]
+        pool_size: Option<u32>,
+        /// Static the task waits on for input
+        #[clap(long, value_enum)]
+        input: Option<Input>,
+        /// Type of the input messages
+        #[clap(long, value_name = "TYPE", default_value = "u32", requires = "input")]
+        message: String,
+        /// Messages the channel holds (default: 4)
+        #[clap(long, value_name = "N")]
+        capacity: Option<usize>,
+    },
+    /// Add a driver crate generic over the embedded-hal-async I2C or SPI traits to the workspace
+    Driver {
+        /// Name of the crate, eg: bme280
+        name: String,
+        /// Bus the device is on (default: i2c)
+        #[clap(long, value_enum)]
+        bus: Option<Bus>,
+        /// Create the crate in the given path instead of the workspace's crates directory
+        #[clap(long)]
+        standalone: bool,
+    },
+}
+
+pub async fn generate(cmd: GenerateCommand) -> anyhow::Result<()> {
+    match cmd.what {
+        Generate::Peripheral { spec, chip_data } => {
+            let root = Project::discover(&cmd.path)?.root;
+            let lock = Lock::read(&root)?;
+            let Some(chip_data) = chip_data.or(Config::load()?.chip_data) else {
+                anyhow::bail!(ValidationError(
+                    "Peripherals are checked against the chip data, pass --chip-data <PATH> or \
+                     set it with `embassy-cli config set chip-data <PATH>`"
+                        .into()
+                ));
+            };
+
+            // rendered in a scratch directory, like `regen`, and only the new files are copied
+            let scratch =
+                std::env::temp_dir().join(format!("embassy-cli-generate-{}", std::process::id()));
+            if scratch.exists() {
+                std::fs::remove_dir_all(&scratch)?;
+            }
+            std::fs::create_dir_all(&scratch)?;
+            let result = add_peripheral(&root, lock, spec, &chip_data, &scratch).await;
+            std::fs::remove_dir_all(&scratch)?;
+
+            result
+        }
+        Generate::Task {
+            name,
+            pool_size,
+            input,
+            message,
+            capacity,
+        } => {
+            if capacity.is_some() && input != Some(Input::Channel) {
+                anyhow::bail!(ValidationError(
+                    "--capacity only applies to --input channel".into()
+                ));
+            }
+            let task = Task {
+                name,
+                pool_size,
+                input,
+                message,
+                capacity: capacity.unwrap_or(4),
+            };
+            add_task(&Project::discover(&cmd.path)?, &task)
+        }
+        Generate::Driver {
+            name,
+            bus,
+            standalone,
+        } => {
+            let driver = Driver {
+                name,
+                bus: bus.unwrap_or_default(),
+                workspace: !standalone,
+            };
+
+            if standalone {
+                driver
+                    .validate(&[])
+                    .map_err(|e| ValidationError(format!("Invalid driver: {e}")))?;
+                return add_driver(&cmd.path, &driver);
+            }
+            let project = Project::discover(&cmd.path)
+                .ok()
+                .filter(|project| project.workspace);
+            let Some(project) = project else {
+                anyhow::bail!(ValidationError(
+                    "No workspace found, create one with `embassy-cli create --workspace` or \
+                     pass --standalone"
+                        .into()
+                ));
+            };
+            driver
+                .validate(&dependency_names(&project)?)
+                .map_err(|e| ValidationError(format!("Invalid driver: {e}")))?;
+            add_driver(&project.root, &driver)?;
+            wire_driver(&project, &driver)
+        }
+    }
+}
+
+/// writes the crate of the driver to `{parent}/crates/{name}`, or `{parent}/{name}` when it is
+/// standalone
+fn add_driver(parent: &Path, driver: &Driver) -> anyhow::Result<()> {
+    let relative = driver_path(driver);
+    let dir = parent.join(&relative);
+    if dir.exists() {
+        anyhow::bail!(ValidationError(format!("{} already exists", dir.display())));
+    }
+
+    println!("Added driver {}", driver.name);
+    for (file, content) in driver.files() {
+        let path = dir.join(&file);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)?;
+        println!("  {}", relative.join(&file).display());
+    }
+
+    Ok(())
+}
+
+fn driver_path(driver: &Driver) -> PathBuf {
+    match driver.workspace {
+        true => Path::new("crates").join(&driver.name),
+        false => PathBuf::from(&driver.name),
+    }
+}
+
+/// crates the workspace and the application depend on, a driver cannot take their name
+fn dependency_names(project: &Project) -> anyhow::Result<Vec<String>> {
+    let mut names = vec![];
+    for manifest in [project.root_manifest(), project.app_manifest()?] {
+        let mut doc = read_manifest(&manifest)?;
+        for table in dependency_tables(&mut doc) {
+            names.extend(table.iter().map(|(name, _)| name.to_owned()));
+        }
+    }
+    Ok(names)
+}
+
+/// makes the driver a workspace member and a dependency of the application
+fn wire_driver(project: &Project, driver: &Driver) -> anyhow::Result<()> {
+    let name = driver.name.as_str();
+    // manifests use forward slashes whatever the platform
+    let member = format!("crates/{name}");
+
+    let root_manifest = project.root_manifest();
+    let mut root = read_manifest(&root_manifest)?;
+    let members = table_mut(&mut root, &["workspace"])?
+        .entry("members")
+        .or_insert(toml_edit::value(Array::new()))
+        .as_array_mut()
+        .ok_or(anyhow::anyhow!("`workspace.members` is not an array"))?;
+    // `crates/*` already covers it
+    let covered = members
+        .iter()
+        .any(|m| m.as_str() == Some(&member) || m.as_str() == Some("crates/*"));
+    if !covered {
+        members.push(member.as_str());
+    }
+    let mut entry = InlineTable::new();
+    entry.insert("path", member.as_str().into());
+    upsert_dependency(
+        table_mut(&mut root, &["workspace", "dependencies"])?,
+        name,
+        entry,
+    );
+    write_manifest(&root_manifest, &root)?;
+    println!("  Cargo.toml");
+
+    let app_manifest = project.app_manifest()?;
+    let mut app = read_manifest(&app_manifest)?;
+    let mut member_entry = InlineTable::new();
+    member_entry.insert("workspace", true.into());
+    // logs through defmt like the application does
+    let defmt = app
+        .get("dependencies")
+        .and_then(|d| d.as_table_like())
+        .is_some_and(|d| d.contains_key("defmt"));
+    if defmt {
+        member_entry.insert("features", Array::from_iter(["defmt"]).into());
+    }
+    upsert_dependency(table_mut(&mut app, &["dependencies"])?, name, member_entry);
+    write_manifest(&app_manifest, &app)?;
+    println!(
+        "  {}",
+        app_manifest
+            .strip_prefix(&project.root)
+            .unwrap_or(&app_manifest)
+            .display()
+    );
+    println!();
+    println!("The workspace builds for the chip, run the driver's tests on the host with:");
+    println!("  cargo test -p {name} --target $(rustc -vV | sed -n 's/host: //p')");
+
+    Ok(())
+}
+
+fn add_task(project: &Project, task: &Task) -> anyhow::Result<()> {
+    task.validate()
+        .map_err(|e| ValidationError(format!("Invalid task: {e}")))?;
+
+    let manifest = project.app_manifest()?;
+    let app = manifest.parent().unwrap_or(&project.root);
+    let main = app.join("src").join("main.rs");
+    let module = app.join("src").join(format!("{}.rs", task.name));
+    if module.exists() || module.with_extension("").join("mod.rs").exists() {
+        anyhow::bail!(ValidationError(format!(
+            "Module {} already exists",
+            task.name
+        )));
+    }
+
+    // the logging crate the project depends on
+    let doc = read_manifest(&manifest)?;
+    let dependencies = doc.get("dependencies").and_then(|d| d.as_table_like());
+    let depends = |name: &str| dependencies.is_some_and(|d| d.contains_key(name));
+    let logging = match (depends("defmt"), depends("log")) {
+        (true, _) => Logging::Defmt,
+        (false, true) => Logging::Log,
+        (false, false) => Logging::None,
+    };
+
+    let relative = |path: &Path| path.strip_prefix(&project.root).unwrap_or(path).to_owned();
+    let source = std::fs::read_to_string(&main)
+        .map_err(|e| anyhow::anyhow!("Could not read {}: {e}", main.display()))?;
+    let edited = tasks::wire(&source, &task.name, &task.call(), logging).map_err(|e| {
+        ValidationError(format!(
+            "Could not add the task to {}: {e}",
+            relative(&main).display()
+        ))
+    })?;
+
+    // recorded in the lock so `regen` adds the task again, main.rs still counts as generated
+    // when it was before the edit
+    let mut lock = Lock::read_if_exists(&project.root)?;
+    let generated = lock
+        .as_ref()
+        .is_some_and(|lock| lock.is_generated(&project.root, &relative(&main)));
+
+    std::fs::write(&module, task.render(logging))?;
+    println!("Added task {}", task.name);
+    println!("  {}", relative(&module).display());
+    if let Some(edited) = &edited {
+        std::fs::write(&main, edited)?;
+        println!("  {}", relative(&main).display());
+    }
+    if let Some(lock) = &mut lock {
+        lock.rehash(&project.root, &relative(&module))?;
+        if generated {
+            lock.rehash(&project.root, &relative(&main))?;
+        }
+        lock.inputs.tasks.push(task.clone());
+        lock.write(&project.root)?;
+    }
+    if edited.is_none() {
+        println!();
+        println!(
+            "No spawner was found in {}, add the task by hand:",
+            relative(&main).display()
+        );
+        println!("  mod {};", task.name);
+        println!("  {}", templates::spawn("spawner", &task.call(), logging));
+    }
+    if task.input.is_some() && !depends("embassy-sync") {
+        eprintln!("warning: embassy-sync is missing, add it with `embassy-cli add embassy-sync`");
+    }
+
+    Ok(())
+}
+
+async fn add_peripheral(
+    root: &Path,
+    mut lock: Lock,
+    spec: Peripheral,
+    chip_data: &Path,
+    scratch: &Path,
+) -> anyhow::Result<()> {
+    let mut cfg = lock.config(root)?;
+    let board = cfg.board.as_deref().and_then(|name| DATA.board(name));
+    let peripheral = peripherals::resolve_all(
+        chip_data,
+        cfg.vendor,
+        &cfg.mcu,
+        board,
+        &cfg.peripherals,
+        &[spec],
+        cfg.executor_irq.as_deref(),
+    )?
+    .remove(0);
+    let vendor = cfg.vendor;
+    let logging = cfg.logging;
+    cfg.peripherals.push(peripheral.clone());
+
+    let builder = generate::prepare(cfg).await?;
+    generate::write(&builder, scratch)?;
+    let generated = scratch.join(&lock.name);
+    let fresh = Lock::read(&generated)?;
+
+    let Some(main) = fresh
+        .files
+        .keys()
+        .find(|f| f.ends_with("src/main.rs"))
+        .cloned()
+    else {
+        anyhow::bail!("The project has no src/main.rs");
+    };
+    let module = main.with_file_name(format!("{}.rs", peripheral.module()));
+    if root.join(&module).exists() {
+        anyhow::bail!(ValidationError(format!(
+            "{} already exists",
+            module.display()
+        )));
+    }
+    std::fs::copy(generated.join(&module), root.join(&module))?;
+    lock.files
+        .insert(module.clone(), fresh.files[&module].clone());
+
+    // main.rs is only rewritten when it is still the generated one
+    let unmodified = lock.is_generated(root, &main);
+    if unmodified {
+        std::fs::copy(generated.join(&main), root.join(&main))?;
+        lock.files.insert(main.clone(), fresh.files[&main].clone());
+    }
+    lock.inputs.peripherals.push(peripheral.clone());
+    lock.write(root)?;
+
+    println!("Added {peripheral}");
+    println!("  {}", module.display());
+    if unmodified {
+        println!("  {}", main.display());
+    } else {
+        println!();
+        println!(
+            "{} was modified since it was generated, add the peripheral by hand:",
+            main.display()
+        );
+        println!("  mod {};", peripheral.module());
+        println!("  {}", peripheral.init(vendor));
+        println!("  {}", peripheral.spawn(logging));
+    }
+
+    Ok(())
+}
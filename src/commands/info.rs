@@ -0,0 +1,100 @@
+use clap::Parser;
+
+use crate::{
+    commands::create::Vendor,
+    config::Config,
+    generator::{
+        clocks::format_freq,
+        data::{DataError, DATA},
+        templates::{crate_features, runner, CrateOptions},
+    },
+};
+
+#[derive(Parser)]
+pub struct InfoCommand {
+    /// MCU or board to show details for
+    mcu: String,
+}
+
+pub fn info(cmd: InfoCommand) -> anyhow::Result<()> {
+    if let Some(board) = DATA.board(&cmd.mcu) {
+        println!("{}", board.name());
+        println!("  {:<12} {}", "description", board.description());
+        println!("  {:<12} {}", "probe", board.probe().unwrap_or("none"));
+        if let Some(hse) = board.hse() {
+            let kind = match hse.bypass {
+                true => "clock",
+                false => "crystal",
+            };
+            println!("  {:<12} {} {kind}", "hse", format_freq(hse.freq));
+        }
+        for led in board.leds() {
+            println!("  {:<12} {} on {}", "led", led.name, led.pin);
+        }
+        for button in board.buttons() {
+            println!("  {:<12} {} on {}", "button", button.name, button.pin);
+        }
+        if let Some(uart) = board.uart() {
+            println!(
+                "  {:<12} {} tx {} rx {}",
+                "console", uart.peripheral, uart.tx, uart.rx
+            );
+        }
+        println!();
+    }
+
+    let query = DATA
+        .board(&cmd.mcu)
+        .map_or(cmd.mcu.as_str(), |board| board.mcu());
+    let mcu = DATA
+        .normalize(query)
+        .unwrap_or_else(|| query.to_lowercase());
+    let Some(vendor) = DATA.vendor_of(&mcu) else {
+        return Err(DataError::InvalidMcu {
+            suggestions: DATA.suggestions(&mcu),
+            mcu,
+        }
+        .into());
+    };
+
+    let flavor = DATA.flavor(&mcu)?;
+    let (flash, ram) = DATA.memory(&mcu);
+    let flash = match (flash, vendor) {
+        (Some(flash), _) => format!("{flash} KiB"),
+        (None, Vendor::Rp) => "external".into(),
+        (None, _) => "unknown".into(),
+    };
+    let ram = ram.map_or("unknown".into(), |ram| format!("{ram} KiB"));
+
+    println!("{mcu}");
+    println!("  {:<12} {}", "vendor", String::from(vendor));
+    println!("  {:<12} {}", "family", DATA.family(&mcu)?);
+    println!("  {:<12} {}", "flavor", flavor.regex());
+    println!("  {:<12} {}", "target", DATA.target(&mcu)?);
+    println!("  {:<12} {flash}", "flash");
+    println!("  {:<12} {ram}", "ram");
+    let time = DATA.time(&mcu)?;
+    println!("  {:<12} {}", "time driver", time.drivers().join(", "));
+    println!("  {:<12} {} Hz", "tick rate", time.tick_hz());
+    if let Some(clocks) = DATA.clocks(&mcu) {
+        println!(
+            "  {:<12} up to {}",
+            "sysclk",
+            format_freq(clocks.sysclk_max())
+        );
+    }
+    println!(
+        "  {:<12} {}",
+        "runner",
+        runner(Config::load()?.runner()?, &mcu)
+    );
+    println!("  features");
+    for (name, features) in crate_features(vendor, &mcu, &CrateOptions::defaults(&mcu)?) {
+        match features.is_empty() {
+            true => println!("    {name:<18} -"),
+            false => println!("    {name:<18} {}", features.join(", ")),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+
+use crate::{commands::create::Vendor, generator::data::DATA};
+
+#[derive(Parser)]
+pub struct ListCommand {
+    #[command(subcommand)]
+    what: ListSubcommand,
+    /// Output format
+    #[clap(short, long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+}
+
+#[derive(Subcommand)]
+enum ListSubcommand {
+    /// List supported vendors
+    Vendors,
+    /// List supported MCUs
+    Mcus {
+        /// Only list MCUs of this vendor
+        #[clap(short, long)]
+        vendor: Option<Vendor>,
+        /// Only list MCUs of this family, eg: "f4" or "nrf52"
+        #[clap(long)]
+        family: Option<String>,
+        /// Only list MCUs matching this regex
+        #[clap(long)]
+        filter: Option<String>,
+    },
+    /// List supported compilation targets
+    Targets,
+    /// List development boards known to `create --board`
+    Boards,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Table,
+    Plain,
+    Json,
+}
+
+pub fn list(cmd: ListCommand) -> anyhow::Result<()> {
+    match cmd.what {
+        ListSubcommand::Vendors => {
+            let rows = DATA
+                .vendor_list()
+                .into_iter()
+                .map(|vendor| {
+                    let vendor = Vendor::from_str(&vendor, true).unwrap(); // should be safe
+                    let mcus = DATA.mcu_list(vendor)?.len();
+                    Ok(vec![String::from(vendor), mcus.to_string()])
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            print_rows(cmd.format, &["vendor", "mcus"], rows);
+        }
+        ListSubcommand::Mcus {
+            vendor,
+            family,
+            filter,
+        } => {
+            let filter = filter.map(|f| regex::Regex::new(&f)).transpose()?;
+            let family = family.map(|f| short_family(&f.to_lowercase()).to_owned());
+            let vendors = match vendor {
+                Some(vendor) => vec![vendor],
+                None => Vendor::value_variants().to_vec(),
+            };
+
+            let mut rows = vec![];
+            for vendor in vendors {
+                for mcu in DATA.mcu_list(vendor)? {
+                    let mcu_family = DATA.family(&mcu).unwrap_or_default();
+                    if let Some(family) = &family {
+                        if short_family(&mcu_family) != family {
+                            continue;
+                        }
+                    }
+                    if let Some(filter) = &filter {
+                        if !filter.is_match(&mcu) {
+                            continue;
+                        }
+                    }
+
+                    let target = DATA.target(&mcu).unwrap_or_default();
+                    rows.push(vec![mcu, vendor.into(), mcu_family, target]);
+                }
+            }
+
+            print_rows(cmd.format, &["mcu", "vendor", "family", "target"], rows);
+        }
+        ListSubcommand::Targets => {
+            let rows = DATA
+                .target_list()
+                .into_iter()
+                .map(|(target, regexes)| {
+                    let families = regexes
+                        .iter()
+                        .map(|r| r.trim_end_matches(".*"))
+                        .collect::<Vec<_>>();
+                    vec![target, families.join(", ")]
+                })
+                .collect();
+
+            print_rows(cmd.format, &["target", "families"], rows);
+        }
+        ListSubcommand::Boards => {
+            let rows = DATA
+                .boards()
+                .iter()
+                .map(|board| {
+                    vec![
+                        board.name().to_owned(),
+                        board.mcu().to_owned(),
+                        board.description().to_owned(),
+                    ]
+                })
+                .collect();
+
+            print_rows(cmd.format, &["board", "mcu", "description"], rows);
+        }
+    }
+
+    Ok(())
+}
+
+/// prints rows as an aligned table, the first column only, or a JSON array of objects
+/// family without the "stm32" prefix, so "f4" and "stm32f4" name the same family
+fn short_family(family: &str) -> &str {
+    family.strip_prefix("stm32").unwrap_or(family)
+}
+
+fn print_rows(format: Format, headers: &[&str], rows: Vec<Vec<String>>) {
+    match format {
+        Format::Table => {
+            let widths = headers
+                .iter()
+                .enumerate()
+                .map(|(i, h)| rows.iter().map(|r| r[i].len()).fold(h.len(), usize::max))
+                .collect::<Vec<_>>();
+
+            let print_row = |row: &[&str]| {
+                let line = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{cell:<width$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                println!("{}", line.trim_end());
+            };
+
+            print_row(headers);
+            for row in &rows {
+                print_row(&row.iter().map(String::as_str).collect::<Vec<_>>());
+            }
+        }
+        Format::Plain => {
+            for row in rows {
+                println!("{}", row[0]);
+            }
+        }
+        Format::Json => {
+            let objects = rows
+                .into_iter()
+                .map(|row| {
+                    headers
+                        .iter()
+                        .map(|h| h.to_string())
+                        .zip(row.into_iter().map(|cell| json!(cell)))
+                        .collect::<serde_json::Map<_, _>>()
+                })
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&objects).unwrap());
+        }
+    }
+}
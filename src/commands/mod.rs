@@ -1,14 +1,59 @@
 use clap::Subcommand;
 
-use self::{completion::CompletionCommand, create::CreateCommand};
+use self::{
+    add::AddCommand,
+    completion::{CompleteMcuCommand, CompletionCommand},
+    config::ConfigCommand,
+    create::CreateCommand,
+    doctor::DoctorCommand,
+    generate::GenerateCommand,
+    info::InfoCommand,
+    list::ListCommand,
+    outdated::OutdatedCommand,
+    preset::PresetCommand,
+    regen::RegenCommand,
+    update::UpdateCommand,
+};
 
+pub mod add;
 pub mod completion;
+pub mod config;
 pub mod create;
+pub mod doctor;
+pub mod generate;
+pub mod info;
+pub mod list;
+pub mod outdated;
+pub mod preset;
+pub mod regen;
+pub mod update;
 
 #[derive(Subcommand)]
 pub enum Command {
     /// Create a new Embassy project
-    Create(CreateCommand),
+    Create(Box<CreateCommand>),
+    /// Save and list presets for `create`
+    Preset(PresetCommand),
+    /// Add an embassy crate to an existing project
+    Add(AddCommand),
+    /// Update the embassy revision of an existing project
+    Update(UpdateCommand),
+    /// Generate the project again from its embassy-cli.lock
+    Regen(RegenCommand),
+    /// Generate code in an existing project
+    Generate(GenerateCommand),
+    /// Report how far behind upstream embassy a project is
+    Outdated(OutdatedCommand),
+    /// Check the development environment for common problems
+    Doctor(DoctorCommand),
+    /// List supported vendors, MCUs and targets
+    List(ListCommand),
+    /// Show details about an MCU
+    Info(InfoCommand),
+    /// Get or set defaults in the user and project config files
+    Config(ConfigCommand),
     /// Generate shell completions
     Completion(CompletionCommand),
+    #[command(name = "complete-mcu", hide = true)]
+    CompleteMcu(CompleteMcuCommand),
 }
@@ -0,0 +1,127 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::Parser;
+
+use crate::{
+    generator::generate::is_nightly,
+    git::Git,
+    project::{embassy_dependencies, read_manifest, Project},
+};
+
+#[derive(Parser)]
+pub struct OutdatedCommand {
+    /// Compare against a local embassy checkout instead of upstream
+    #[clap(long)]
+    local: Option<PathBuf>,
+    /// Path to the project
+    #[clap(short, long, default_value = ".")]
+    path: PathBuf,
+}
+
+enum Upstream {
+    Remote,
+    Local(PathBuf),
+}
+
+impl Upstream {
+    async fn head(&self) -> anyhow::Result<String> {
+        match self {
+            Upstream::Remote => Git::get_latest_commit().await,
+            Upstream::Local(checkout) => Git::get_local_head(checkout),
+        }
+    }
+
+    async fn commits_behind(&self, rev: &str) -> anyhow::Result<u64> {
+        match self {
+            Upstream::Remote => Git::get_commits_behind(rev).await,
+            Upstream::Local(checkout) => Git::get_local_commits_behind(checkout, rev),
+        }
+    }
+
+    async fn crate_version(&self, name: &str, head: &str) -> anyhow::Result<String> {
+        match self {
+            Upstream::Remote => Git::get_crate_version(name, head).await,
+            Upstream::Local(checkout) => Git::get_local_crate_version(checkout, name),
+        }
+    }
+
+    /// nightly projects are compared with embassy's nightly toolchain when it has both
+    async fn toolchain_channel(&self, head: &str, nightly: bool) -> anyhow::Result<String> {
+        let channel = match self {
+            Upstream::Remote => Git::get_toolchain_channel(head).await?,
+            Upstream::Local(checkout) => Git::get_local_toolchain_channel(checkout)?,
+        };
+        if !nightly || is_nightly(&channel) {
+            return Ok(channel);
+        }
+
+        match self {
+            Upstream::Remote => Git::get_nightly_toolchain_channel(head).await,
+            Upstream::Local(checkout) => Git::get_local_nightly_toolchain_channel(checkout),
+        }
+    }
+}
+
+pub async fn outdated(cmd: OutdatedCommand) -> anyhow::Result<()> {
+    let project = Project::discover(&cmd.path)?;
+    let upstream = match cmd.local {
+        Some(checkout) => Upstream::Local(checkout),
+        None => Upstream::Remote,
+    };
+
+    let head = upstream.head().await?;
+    match project.pinned_rev()? {
+        Some(rev) => {
+            let behind = upstream.commits_behind(&rev).await?;
+            println!("embassy is pinned to {rev}, {behind} commits behind {head}");
+        }
+        None => println!("embassy is not pinned to a revision, comparing against {head}"),
+    }
+
+    let mut crates = BTreeMap::new();
+    let mut manifests = vec![project.root_manifest()];
+    if project.workspace {
+        manifests.push(project.app_manifest()?);
+    }
+    for manifest in manifests {
+        for (name, version) in embassy_dependencies(&read_manifest(&manifest)?) {
+            let entry = crates.entry(name).or_insert(None);
+            if version.is_some() {
+                *entry = version;
+            }
+        }
+    }
+
+    if !crates.is_empty() {
+        println!();
+        println!("{:<24} {:<12} latest", "crate", "current");
+    }
+    for (name, current) in crates {
+        let latest = upstream.crate_version(&name, &head).await?;
+        let current = current.unwrap_or("-".into());
+        let marker = if current == latest { "" } else { " *" };
+        println!("{name:<24} {current:<12} {latest}{marker}");
+    }
+
+    let toolchain_path = project.root.join("rust-toolchain.toml");
+    if toolchain_path.exists() {
+        let current = read_manifest(&toolchain_path)?
+            .get("toolchain")
+            .and_then(|t| t.get("channel"))
+            .and_then(|c| c.as_str())
+            .map(String::from)
+            .unwrap_or_default();
+        let latest = upstream
+            .toolchain_channel(&head, is_nightly(&current))
+            .await?;
+
+        println!();
+        if current == latest {
+            println!("rust-toolchain.toml channel {current} is up to date");
+        } else {
+            println!("rust-toolchain.toml channel differs: {current} -> {latest}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use clap::{Parser, Subcommand};
+
+use crate::commands::create::CreateCommand;
+
+#[derive(Parser)]
+pub struct PresetCommand {
+    #[command(subcommand)]
+    action: PresetAction,
+}
+
+#[derive(Subcommand)]
+enum PresetAction {
+    /// Answer the `create` questions and save the answers as a preset
+    Save {
+        /// Name to save the preset under
+        #[clap(value_name = "NAME")]
+        preset_name: String,
+        #[command(flatten)]
+        options: Box<CreateCommand>,
+    },
+    /// List saved presets
+    List,
+}
+
+pub fn preset(cmd: PresetCommand) -> anyhow::Result<()> {
+    match cmd.action {
+        PresetAction::Save {
+            preset_name,
+            options,
+        } => {
+            let (_, chosen) = options.resolve(false)?;
+            let path = chosen.save(&preset_name)?;
+            println!("Saved preset {preset_name} to {}", path.display());
+            println!("Use it with `embassy-cli create --preset {preset_name}`");
+        }
+        PresetAction::List => {
+            for name in crate::preset::list()? {
+                println!("{name}");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+
+use crate::{
+    error::ValidationError,
+    generator::{
+        generate,
+        lock::{hash_file, Lock, LOCK_FILE},
+    },
+    project::Project,
+};
+
+#[derive(Parser)]
+pub struct RegenCommand {
+    /// Overwrite files that were modified since the project was generated, or regenerate a
+    /// project generated by another version of embassy-cli
+    #[clap(long)]
+    force: bool,
+    /// Path to the project
+    #[clap(short, long, default_value = ".")]
+    path: PathBuf,
+}
+
+pub async fn regen(cmd: RegenCommand) -> anyhow::Result<()> {
+    let root = Project::discover(&cmd.path)?.root;
+    let lock = Lock::read(&root)?;
+    // the templates change between versions, the whole project would be rewritten
+    let version = env!("CARGO_PKG_VERSION");
+    if lock.embassy_cli != version && !cmd.force {
+        anyhow::bail!(ValidationError(format!(
+            "The project was generated by embassy-cli {}, this is {version}, pass --force to \
+             regenerate it with the templates of {version}",
+            lock.embassy_cli
+        )));
+    }
+
+    // render into a scratch directory first, so nothing is touched when files were modified
+    let scratch = std::env::temp_dir().join(format!("embassy-cli-regen-{}", std::process::id()));
+    if scratch.exists() {
+        std::fs::remove_dir_all(&scratch)?;
+    }
+    std::fs::create_dir_all(&scratch)?;
+    let result = regenerate(&cmd, &root, &lock, &scratch).await;
+    std::fs::remove_dir_all(&scratch)?;
+
+    result
+}
+
+async fn regenerate(
+    cmd: &RegenCommand,
+    root: &Path,
+    lock: &Lock,
+    scratch: &Path,
+) -> anyhow::Result<()> {
+    let builder = generate::prepare(lock.config(root)?).await?;
+    let warnings = generate::write(&builder, scratch)?;
+    let generated = scratch.join(&lock.name);
+    let mut fresh = Lock::read(&generated)?;
+    // the fresh lock has the template relative to the scratch directory
+    fresh.inputs.template = lock.inputs.template.clone();
+
+    let mut modified = vec![];
+    let mut changed = vec![];
+    for (file, hash) in &fresh.files {
+        let path = root.join(file);
+        if !path.exists() {
+            changed.push(file);
+            continue;
+        }
+
+        let current = hash_file(&path)?;
+        if &current == hash {
+            continue;
+        }
+        // files the lock does not know about were created by the user
+        if lock.files.get(file) != Some(&current) {
+            modified.push(file);
+        }
+        changed.push(file);
+    }
+
+    if !modified.is_empty() && !cmd.force {
+        let files = modified
+            .iter()
+            .map(|f| format!("  {}", f.display()))
+            .collect::<Vec<_>>();
+        anyhow::bail!(ValidationError(format!(
+            "These files were modified since the project was generated, pass --force to overwrite them:\n{}",
+            files.join("\n")
+        )));
+    }
+
+    for file in &changed {
+        let path = root.join(file);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(generated.join(file), path)?;
+    }
+    fresh.write(root)?;
+
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+    if changed.is_empty() {
+        println!("All files match {LOCK_FILE}");
+    } else {
+        println!("Regenerated {} file(s)", changed.len());
+        for file in changed {
+            println!("  {}", file.display());
+        }
+    }
+
+    Ok(())
+}
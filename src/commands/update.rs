@@ -0,0 +1,218 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::Parser;
+use toml_edit::{Document, Item, Value};
+
+use crate::{
+    generator::{
+        generate::{is_nightly, resolve_channel},
+        lock::Lock,
+    },
+    git::Git,
+    project::{
+        dependency_tables, is_embassy_patch, read_manifest, set_string, write_manifest, Project,
+    },
+};
+
+#[derive(Parser)]
+pub struct UpdateCommand {
+    /// Embassy commit to update to
+    #[clap(long, conflicts_with_all = ["tag", "latest"])]
+    rev: Option<String>,
+    /// Embassy tag to update to, pinned as the commit it points at
+    #[clap(long, conflicts_with = "latest")]
+    tag: Option<String>,
+    /// Update to the latest commit on main (default)
+    #[clap(long)]
+    latest: bool,
+    /// Path to the project
+    #[clap(short, long, default_value = ".")]
+    path: PathBuf,
+}
+
+pub async fn update(cmd: UpdateCommand) -> anyhow::Result<()> {
+    let project = Project::discover(&cmd.path)?;
+    // tags are resolved to their commit, so the patches, `add` and the lock all agree on a rev
+    let (reference, target) = match (cmd.rev, cmd.tag) {
+        (Some(rev), _) => (rev.clone(), format!("rev {rev}")),
+        (None, Some(tag)) => {
+            let rev = Git::get_commit(&tag).await?;
+            (rev.clone(), format!("tag {tag} ({rev})"))
+        }
+        (None, None) => {
+            let rev = Git::get_latest_commit().await?;
+            (rev.clone(), format!("rev {rev}"))
+        }
+    };
+
+    let mut manifests = vec![project.root_manifest()];
+    if project.workspace {
+        manifests.push(project.app_manifest()?);
+    }
+    let mut docs = manifests
+        .iter()
+        .map(|path| read_manifest(path))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut versions = HashMap::new();
+    for doc in &mut docs {
+        for deps in dependency_tables(doc) {
+            for (name, dep) in deps.iter_mut() {
+                if name.starts_with("embassy-") && version_mut(dep).is_some() {
+                    versions.insert(name.get().to_owned(), String::new());
+                }
+            }
+        }
+    }
+    for (name, version) in versions.iter_mut() {
+        *version = Git::get_crate_version(name.as_str(), &reference).await?;
+    }
+
+    let mut changes = vec![];
+    for (name, old) in update_patches(&mut docs[0], &reference) {
+        changes.push(format!(
+            "[patch.crates-io] {name}: {old} -> rev {reference}"
+        ));
+    }
+    for doc in &mut docs {
+        for deps in dependency_tables(doc) {
+            for (name, dep) in deps.iter_mut() {
+                let (Some(new), Some(value)) = (versions.get(name.get()), version_mut(dep)) else {
+                    continue;
+                };
+                match set_string(value, new) {
+                    Some(old) if &old == new => {}
+                    old => changes.push(format!(
+                        "{}: {} -> {new}",
+                        name.get(),
+                        old.unwrap_or_default()
+                    )),
+                }
+            }
+        }
+    }
+
+    let toolchain_path = project.root.join("rust-toolchain.toml");
+    // rewritten files that were still as generated keep counting as generated in the lock
+    let mut lock = Lock::read_if_exists(&project.root)?;
+    let generated = manifests
+        .iter()
+        .chain([&toolchain_path])
+        .map(|path| path.strip_prefix(&project.root).unwrap_or(path).to_owned())
+        .filter(|file| {
+            lock.as_ref()
+                .is_some_and(|lock| lock.is_generated(&project.root, file))
+        })
+        .collect::<Vec<_>>();
+    let mut toolchain_channel = None;
+
+    if toolchain_path.exists() {
+        let mut toolchain = read_manifest(&toolchain_path)?;
+        if let Some(value) = toolchain
+            .get_mut("toolchain")
+            .and_then(|t| t.get_mut("channel"))
+            .and_then(|c| c.as_value_mut())
+        {
+            // the toolchain asked for when the project was created, without a lock a nightly
+            // project keeps its nightly features and any other channel but stable was picked
+            let request = match &lock {
+                Some(lock) => lock.inputs.toolchain.clone(),
+                None => match value.as_str() {
+                    Some(channel) if is_nightly(channel) => Some("nightly".into()),
+                    Some("stable") | None => None,
+                    Some(channel) => Some(channel.to_owned()),
+                },
+            };
+            let channel = resolve_channel(request.as_deref(), &reference).await?;
+            toolchain_channel = Some(channel.clone());
+            match set_string(value, &channel) {
+                Some(old) if old == channel => {}
+                old => changes.push(format!(
+                    "rust-toolchain.toml: {} -> {channel}",
+                    old.unwrap_or_default()
+                )),
+            }
+        }
+        write_manifest(&toolchain_path, &toolchain)?;
+    }
+
+    for (path, doc) in manifests.iter().zip(&docs) {
+        write_manifest(path, doc)?;
+    }
+    if let Some(lock) = &mut lock {
+        for file in &generated {
+            lock.rehash(&project.root, file)?;
+        }
+        lock.resolved.rev = Some(reference.clone());
+        if let Some(channel) = toolchain_channel {
+            lock.resolved.toolchain = channel;
+        }
+        lock.resolved.crates.extend(versions);
+        lock.write(&project.root)?;
+    }
+
+    if changes.is_empty() {
+        println!("Already up to date with embassy {target}");
+    } else {
+        println!("Updated embassy to {target}");
+        for change in changes {
+            println!("  {change}");
+        }
+    }
+
+    Ok(())
+}
+
+/// points every embassy patch at `rev`, returns the crates whose pin changed with their previous
+/// pin
+fn update_patches(doc: &mut Document, rev: &str) -> Vec<(String, String)> {
+    let Some(patches) = doc
+        .get_mut("patch")
+        .and_then(|p| p.get_mut("crates-io"))
+        .and_then(|p| p.as_table_like_mut())
+    else {
+        return vec![];
+    };
+
+    let mut previous = vec![];
+    for (name, patch) in patches.iter_mut() {
+        let Some(patch) = patch.as_table_like_mut() else {
+            continue;
+        };
+        if !is_embassy_patch(name.get(), patch) {
+            continue;
+        }
+
+        let old = ["rev", "tag", "branch"].into_iter().find_map(|k| {
+            let value = patch.get(k)?.as_str()?;
+            Some(format!("{k} {value}"))
+        });
+        if old.as_deref() == Some(&format!("rev {rev}")) {
+            continue;
+        }
+
+        // pins written by older versions may use a tag or a branch
+        patch.remove("tag");
+        patch.remove("branch");
+        match patch.get_mut("rev").and_then(|v| v.as_value_mut()) {
+            Some(value) => {
+                set_string(value, rev);
+            }
+            None => {
+                patch.insert("rev", toml_edit::value(rev));
+                patch.fmt();
+            }
+        }
+
+        previous.push((name.get().to_owned(), old.unwrap_or("unpinned".into())));
+    }
+
+    previous
+}
+
+fn version_mut(dep: &mut Item) -> Option<&mut Value> {
+    if dep.is_str() {
+        return dep.as_value_mut();
+    }
+    dep.as_table_like_mut()?.get_mut("version")?.as_value_mut()
+}
@@ -0,0 +1,273 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde::Deserialize;
+use toml_edit::Document;
+
+use crate::{
+    commands::create::{Editor, Executor, LogLevel, Logging, Panic, Runner, Vendor},
+    error::ValidationError,
+    git::EMBASSY_GIT,
+    project::{read_manifest, write_manifest},
+};
+
+/// name of the repo-local configuration file
+pub const PROJECT_CONFIG: &str = ".embassy-cli.toml";
+
+/// keys accepted in the configuration files
+pub const KEYS: [&str; 13] = [
+    "vendor",
+    "runner",
+    "workspace",
+    "pin",
+    "upstream",
+    "logging",
+    "log-level",
+    "panic",
+    "executor",
+    "editor",
+    "license",
+    "toolchain",
+    "chip-data",
+];
+
+/// defaults for `create`, every key is optional
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub vendor: Option<String>,
+    pub runner: Option<String>,
+    pub workspace: Option<bool>,
+    pub pin: Option<bool>,
+    pub upstream: Option<String>,
+    pub logging: Option<String>,
+    pub log_level: Option<String>,
+    pub panic: Option<String>,
+    pub executor: Option<String>,
+    pub editor: Option<String>,
+    pub license: Option<String>,
+    pub toolchain: Option<String>,
+    /// stm32-data style chip description, or a directory of them, peripherals are checked against
+    pub chip_data: Option<PathBuf>,
+}
+
+impl Config {
+    /// defaults used when neither config file sets a key
+    pub fn builtin() -> Self {
+        Self {
+            vendor: None,
+            runner: Some(name_of(Runner::default())),
+            workspace: Some(false),
+            pin: Some(true),
+            upstream: Some(EMBASSY_GIT.into()),
+            logging: Some(name_of(Logging::default())),
+            log_level: Some(name_of(LogLevel::default())),
+            panic: Some(name_of(Panic::default())),
+            executor: Some(name_of(Executor::default())),
+            editor: Some(name_of(Editor::default())),
+            license: None,
+            toolchain: None,
+            chip_data: None,
+        }
+    }
+
+    /// built-in defaults, overridden by the user config and then the project config
+    pub fn load() -> anyhow::Result<Self> {
+        let mut config = Self::builtin();
+        if let Some(path) = user_path() {
+            config = config.merge(Self::read(&path)?);
+        }
+        if let Some(path) = project_path() {
+            config = config.merge(Self::read(&path)?);
+        }
+
+        Ok(config)
+    }
+
+    /// reads a config file, a missing file is an empty config
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| {
+            anyhow!(ValidationError(format!(
+                "Invalid config {}: {e}",
+                path.display()
+            )))
+        })
+    }
+
+    /// keys set in `other` take precedence
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            vendor: other.vendor.or(self.vendor),
+            runner: other.runner.or(self.runner),
+            workspace: other.workspace.or(self.workspace),
+            pin: other.pin.or(self.pin),
+            upstream: other.upstream.or(self.upstream),
+            logging: other.logging.or(self.logging),
+            log_level: other.log_level.or(self.log_level),
+            panic: other.panic.or(self.panic),
+            executor: other.executor.or(self.executor),
+            editor: other.editor.or(self.editor),
+            license: other.license.or(self.license),
+            toolchain: other.toolchain.or(self.toolchain),
+            chip_data: other.chip_data.or(self.chip_data),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(match key {
+            "vendor" => self.vendor.clone(),
+            "runner" => self.runner.clone(),
+            "workspace" => self.workspace.map(|w| w.to_string()),
+            "pin" => self.pin.map(|p| p.to_string()),
+            "upstream" => self.upstream.clone(),
+            "logging" => self.logging.clone(),
+            "log-level" => self.log_level.clone(),
+            "panic" => self.panic.clone(),
+            "executor" => self.executor.clone(),
+            "editor" => self.editor.clone(),
+            "license" => self.license.clone(),
+            "toolchain" => self.toolchain.clone(),
+            "chip-data" => self.chip_data.as_ref().map(|p| p.display().to_string()),
+            _ => return Err(unknown_key(key)),
+        })
+    }
+
+    pub fn vendor(&self) -> anyhow::Result<Option<Vendor>> {
+        self.vendor
+            .as_deref()
+            .map(|v| parse_enum("vendor", v))
+            .transpose()
+    }
+
+    pub fn runner(&self) -> anyhow::Result<Runner> {
+        parse_or_default("runner", &self.runner)
+    }
+
+    pub fn logging(&self) -> anyhow::Result<Logging> {
+        parse_or_default("logging", &self.logging)
+    }
+
+    pub fn log_level(&self) -> anyhow::Result<LogLevel> {
+        parse_or_default("log-level", &self.log_level)
+    }
+
+    pub fn panic(&self) -> anyhow::Result<Panic> {
+        parse_or_default("panic", &self.panic)
+    }
+
+    pub fn executor(&self) -> anyhow::Result<Executor> {
+        parse_or_default("executor", &self.executor)
+    }
+
+    pub fn editor(&self) -> anyhow::Result<Editor> {
+        parse_or_default("editor", &self.editor)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/embassy-cli/config.toml`, falling back to `~/.config`
+pub fn user_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_dir.join("embassy-cli").join("config.toml"))
+}
+
+/// closest `.embassy-cli.toml` in the current directory or one of its parents
+pub fn project_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG))
+        .find(|path| path.is_file())
+}
+
+/// validates `value` for `key` and writes it to the config file at `path`, keeping its formatting
+pub fn set(path: &Path, key: &str, value: &str) -> anyhow::Result<()> {
+    let value = validate(key, value)?;
+
+    let mut doc = match path.exists() {
+        true => read_manifest(path)?,
+        false => Document::new(),
+    };
+    doc[key] = toml_edit::value(value);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    write_manifest(path, &doc)
+}
+
+fn validate(key: &str, value: &str) -> anyhow::Result<toml_edit::Value> {
+    Ok(match key {
+        "vendor" => name_of(parse_enum::<Vendor>(key, value)?).into(),
+        "runner" => name_of(parse_enum::<Runner>(key, value)?).into(),
+        "logging" => name_of(parse_enum::<Logging>(key, value)?).into(),
+        "log-level" => name_of(parse_enum::<LogLevel>(key, value)?).into(),
+        "panic" => name_of(parse_enum::<Panic>(key, value)?).into(),
+        "executor" => name_of(parse_enum::<Executor>(key, value)?).into(),
+        "editor" => name_of(parse_enum::<Editor>(key, value)?).into(),
+        "workspace" | "pin" => value
+            .parse::<bool>()
+            .map_err(|_| {
+                ValidationError(format!(
+                    "Invalid value \"{value}\" for {key}, expected true or false"
+                ))
+            })?
+            .into(),
+        "upstream" | "license" | "toolchain" => value.into(),
+        "chip-data" => {
+            let path = Path::new(value);
+            if !path.exists() {
+                anyhow::bail!(ValidationError(format!(
+                    "Invalid value \"{value}\" for {key}, no such file or directory"
+                )));
+            }
+            // stored absolute, so it works from any project
+            path.canonicalize()?.display().to_string().into()
+        }
+        _ => return Err(unknown_key(key)),
+    })
+}
+
+fn parse_enum<T: ValueEnum>(key: &str, value: &str) -> anyhow::Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        let expected = T::value_variants()
+            .iter()
+            .map(|v| name_of(v.clone()))
+            .collect::<Vec<_>>();
+        anyhow!(ValidationError(format!(
+            "Invalid value \"{value}\" for {key}, expected one of: {}",
+            expected.join(", ")
+        )))
+    })
+}
+
+fn parse_or_default<T: ValueEnum + Default>(
+    key: &str,
+    value: &Option<String>,
+) -> anyhow::Result<T> {
+    match value {
+        Some(value) => parse_enum(key, value),
+        None => Ok(T::default()),
+    }
+}
+
+pub fn name_of<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .map(|v| v.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+fn unknown_key(key: &str) -> anyhow::Error {
+    anyhow!(ValidationError(format!(
+        "Unknown config key \"{key}\", expected one of: {}",
+        KEYS.join(", ")
+    )))
+}
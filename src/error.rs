@@ -0,0 +1,54 @@
+use serde::Serialize;
+
+use crate::generator::data::DataError;
+
+/// invalid input that is not about chip data, eg: a bad project name or a missing flag
+#[derive(Debug)]
+pub struct ValidationError(pub String);
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// broad cause of a failed command, each one exits with its own code
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorKind {
+    Validation,
+    Network,
+    Filesystem,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn of(err: &anyhow::Error) -> Self {
+        if err.downcast_ref::<DataError>().is_some()
+            || err.downcast_ref::<ValidationError>().is_some()
+        {
+            return Self::Validation;
+        }
+        // reqwest errors can wrap io errors, so look for them first
+        if err.chain().any(|e| e.is::<reqwest::Error>()) {
+            return Self::Network;
+        }
+        if err.chain().any(|e| e.is::<std::io::Error>()) {
+            return Self::Filesystem;
+        }
+
+        Self::Other
+    }
+
+    /// 2 is left to clap for usage errors
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Validation => 3,
+            Self::Network => 4,
+            Self::Filesystem => 5,
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+/// a development board, pins are named the way the HAL's peripherals struct names them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Board {
+    name: String,
+    description: String,
+    mcu: String,
+    /// on-board debug probe, `None` when flashing needs an external one
+    probe: Option<String>,
+    hse: Option<Hse>,
+    leds: Vec<Led>,
+    buttons: Vec<Button>,
+    uart: Option<Uart>,
+}
+
+/// crystal or clock fed to the HSE of an STM32
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Hse {
+    pub freq: u32,
+    /// driven by an external clock, eg: the MCO of the ST-LINK, rather than a crystal
+    pub bypass: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Led {
+    pub name: String,
+    pub pin: String,
+    #[serde(default)]
+    pub active_low: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Button {
+    pub name: String,
+    pub pin: String,
+    #[serde(default)]
+    pub active_low: bool,
+    pub pull: Pull,
+}
+
+/// pull resistor a button needs from the MCU
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+/// UART wired to the console, usually through the debug probe
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Uart {
+    pub peripheral: String,
+    pub tx: String,
+    pub rx: String,
+}
+
+impl Board {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn mcu(&self) -> &str {
+        &self.mcu
+    }
+
+    pub fn probe(&self) -> Option<&str> {
+        self.probe.as_deref()
+    }
+
+    pub fn hse(&self) -> Option<Hse> {
+        self.hse
+    }
+
+    pub fn leds(&self) -> &[Led] {
+        &self.leds
+    }
+
+    pub fn buttons(&self) -> &[Button] {
+        &self.buttons
+    }
+
+    pub fn uart(&self) -> Option<&Uart> {
+        self.uart.as_ref()
+    }
+}
+
+impl Button {
+    /// EXTI line of an STM32 pin, eg: "EXTI13" for "PC13"
+    pub fn exti(&self) -> String {
+        format!(
+            "EXTI{}",
+            self.pin.trim_start_matches(|c: char| !c.is_ascii_digit())
+        )
+    }
+}
+
+impl Pull {
+    pub fn variant(self) -> &'static str {
+        match self {
+            Pull::None => "None",
+            Pull::Up => "Up",
+            Pull::Down => "Down",
+        }
+    }
+}
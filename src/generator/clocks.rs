@@ -0,0 +1,385 @@
+use serde::{Deserialize, Serialize};
+
+use super::data::group_digits;
+
+/// shape of the `embassy_stm32::rcc` config of a family
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rcc {
+    /// F0, F1 and F3: a PLL multiplier without output dividers
+    F013,
+    /// F2, F4 and F7: the system clock comes from PLL P
+    F247,
+    /// G4: the system clock comes from PLL R, boost mode above 150 MHz
+    G4,
+    /// L4: the system clock comes from PLL R
+    L,
+}
+
+/// limits of the PLL and the buses of an STM32 line, frequencies are in Hz
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Clocks {
+    regex: String,
+    rcc: Rcc,
+    hsi: u32,
+    /// fixed divider between the HSI and the PLL
+    #[serde(default = "one")]
+    hsi_div: u32,
+    hse: (u32, u32),
+    sysclk_max: u32,
+    prediv: (u32, u32),
+    mul: (u32, u32),
+    /// PLL input, after the pre-divider
+    pll_in: (u32, u32),
+    /// PLL output, before the dividers
+    vco: (u32, u32),
+    /// dividers from the PLL output to the system clock
+    div: Vec<u32>,
+    apb1_max: u32,
+    apb2_max: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// system clock asked for with `--sysclk`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sysclk {
+    Max,
+    Hz(u32),
+}
+
+impl Sysclk {
+    /// "max" or a frequency
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().eq_ignore_ascii_case("max") {
+            true => Ok(Self::Max),
+            false => parse_freq(value).map(Self::Hz),
+        }
+    }
+}
+
+/// PLL and bus prescalers that reach a system clock
+pub struct RccPlan {
+    rcc: Rcc,
+    hse: Option<u32>,
+    prediv: u32,
+    mul: u32,
+    div: u32,
+    /// PLL Q divider giving 48 MHz for USB, when there is one
+    divq: Option<u32>,
+    apb1: u32,
+    apb2: u32,
+    sysclk: u32,
+}
+
+impl Clocks {
+    pub fn regex(&self) -> &str {
+        &self.regex
+    }
+
+    pub fn sysclk_max(&self) -> u32 {
+        self.sysclk_max
+    }
+
+    /// finds PLL settings within the limits of the line, `hse` is the crystal frequency or
+    /// `None` to run from the HSI
+    pub fn plan(&self, hse: Option<u32>, sysclk: Sysclk) -> Result<RccPlan, String> {
+        if let Some(hse) = hse {
+            if hse < self.hse.0 || hse > self.hse.1 {
+                return Err(format!(
+                    "the HSE must be between {} and {}",
+                    format_freq(self.hse.0),
+                    format_freq(self.hse.1)
+                ));
+            }
+        }
+        let target = match sysclk {
+            Sysclk::Hz(hz) if hz > self.sysclk_max => {
+                return Err(format!(
+                    "{} is above the maximum of {}",
+                    format_freq(hz),
+                    format_freq(self.sysclk_max)
+                ))
+            }
+            Sysclk::Hz(hz) => Some(hz),
+            Sysclk::Max => None,
+        };
+
+        // the pre-divider only applies to the HSE on F0, F1 and F3
+        let (source, prediv) = match (hse, self.rcc) {
+            (Some(hse), _) => (hse, self.prediv),
+            (None, Rcc::F013) => (self.hsi / self.hsi_div, (1, 1)),
+            (None, _) => (self.hsi / self.hsi_div, self.prediv),
+        };
+
+        // highest system clock first, then one with a 48 MHz USB clock, then the highest PLL
+        // input for the least jitter
+        let mut best: Option<((u32, bool, u32), RccPlan)> = None;
+        for p in prediv.0..=prediv.1 {
+            let pll_in = source / p;
+            if !source.is_multiple_of(p) || pll_in < self.pll_in.0 || pll_in > self.pll_in.1 {
+                continue;
+            }
+            for m in self.mul.0..=self.mul.1 {
+                let vco = pll_in as u64 * m as u64;
+                if vco < self.vco.0 as u64 || vco > self.vco.1 as u64 {
+                    continue;
+                }
+                for &d in &self.div {
+                    let out = (vco / d as u64) as u32;
+                    if !vco.is_multiple_of(d as u64)
+                        || out > self.sysclk_max
+                        || target.is_some_and(|t| t != out)
+                    {
+                        continue;
+                    }
+
+                    let divq = match self.rcc {
+                        Rcc::F247 => (2..=15).find(|q| vco == 48_000_000 * q),
+                        _ => None,
+                    };
+                    let key = (out, divq.is_some(), pll_in);
+                    if best.as_ref().is_some_and(|(k, _)| *k >= key) {
+                        continue;
+                    }
+                    best = Some((
+                        key,
+                        RccPlan {
+                            rcc: self.rcc,
+                            hse,
+                            prediv: p,
+                            mul: m,
+                            div: d,
+                            divq: divq.map(|q| q as u32),
+                            apb1: prescaler(out, self.apb1_max),
+                            apb2: prescaler(out, self.apb2_max),
+                            sysclk: out,
+                        },
+                    ));
+                }
+            }
+        }
+
+        let source = match hse {
+            Some(hse) => format!("the {} HSE", format_freq(hse)),
+            None => format!("the {} HSI", format_freq(self.hsi)),
+        };
+        best.map(|(_, plan)| plan).ok_or_else(|| match target {
+            Some(target) => format!(
+                "no PLL setting reaches exactly {} from {source}",
+                format_freq(target)
+            ),
+            None => format!("no PLL setting works from {source}"),
+        })
+    }
+}
+
+impl RccPlan {
+    pub fn sysclk(&self) -> u32 {
+        self.sysclk
+    }
+
+    /// statements building `config`, indented for the body of `main`, `bypass` when the HSE
+    /// is fed a clock rather than a crystal
+    pub fn render(&self, bypass: bool) -> String {
+        let source = match self.hse {
+            Some(_) => "HSE",
+            None => "HSI",
+        };
+        let prediv = format!("PllPreDiv::DIV{}", self.prediv);
+        let mul = format!("PllMul::MUL{}", self.mul);
+
+        let mut lines = vec![
+            "let mut config = embassy_stm32::Config::default();".to_owned(),
+            "{".into(),
+            "    use embassy_stm32::rcc::*;".into(),
+        ];
+        if let Some(hse) = self.hse {
+            lines.push("    use embassy_stm32::time::Hertz;".into());
+            lines.push("".into());
+            lines.push("    config.rcc.hse = Some(Hse {".into());
+            lines.push(format!(
+                "        freq: Hertz({}),",
+                group_digits(hse as u64)
+            ));
+            lines.push(match bypass {
+                true => "        mode: HseMode::Bypass,".into(),
+                false => "        mode: HseMode::Oscillator,".into(),
+            });
+            lines.push("    });".into());
+        } else {
+            lines.push("".into());
+            if self.rcc == Rcc::L {
+                lines.push("    config.rcc.hsi = true;".into());
+            }
+        }
+
+        match self.rcc {
+            Rcc::F013 => {
+                lines.push("    config.rcc.pll = Some(Pll {".into());
+                lines.push(format!("        src: PllSource::{source},"));
+                lines.push(format!("        prediv: {prediv},"));
+                lines.push(format!("        mul: {mul},"));
+                lines.push("    });".into());
+                lines.push("    config.rcc.sys = Sysclk::PLL1_P;".into());
+            }
+            Rcc::F247 => {
+                let divq = match self.divq {
+                    Some(q) => format!("Some(PllQDiv::DIV{q})"),
+                    None => "None".into(),
+                };
+                lines.push(format!("    config.rcc.pll_src = PllSource::{source};"));
+                lines.push("    config.rcc.pll = Some(Pll {".into());
+                lines.push(format!("        prediv: {prediv},"));
+                lines.push(format!("        mul: {mul},"));
+                lines.push(format!("        divp: Some(PllPDiv::DIV{}),", self.div));
+                lines.push(format!("        divq: {divq},"));
+                lines.push("        divr: None,".into());
+                lines.push("    });".into());
+                lines.push("    config.rcc.sys = Sysclk::PLL1_P;".into());
+            }
+            Rcc::G4 | Rcc::L => {
+                lines.push("    config.rcc.pll = Some(Pll {".into());
+                lines.push(format!("        source: PllSource::{source},"));
+                lines.push(format!("        prediv: {prediv},"));
+                lines.push(format!("        mul: {mul},"));
+                lines.push("        divp: None,".into());
+                lines.push("        divq: None,".into());
+                lines.push(format!("        divr: Some(PllRDiv::DIV{}),", self.div));
+                lines.push("    });".into());
+                lines.push("    config.rcc.sys = Sysclk::PLL1_R;".into());
+                if self.rcc == Rcc::G4 && self.sysclk > 150_000_000 {
+                    lines.push("    config.rcc.boost = true;".into());
+                }
+            }
+        }
+        lines.push("    config.rcc.ahb_pre = AHBPrescaler::DIV1;".into());
+        lines.push(format!(
+            "    config.rcc.apb1_pre = APBPrescaler::DIV{};",
+            self.apb1
+        ));
+        lines.push(format!(
+            "    config.rcc.apb2_pre = APBPrescaler::DIV{};",
+            self.apb2
+        ));
+        lines.push("}".into());
+
+        lines
+            .iter()
+            .map(|line| match line.is_empty() {
+                true => "\n".to_owned(),
+                false => format!("    {line}\n"),
+            })
+            .collect()
+    }
+}
+
+/// smallest bus prescaler keeping the bus under `max`
+fn prescaler(sysclk: u32, max: u32) -> u32 {
+    [1, 2, 4, 8, 16]
+        .into_iter()
+        .find(|pre| sysclk / pre <= max)
+        .unwrap_or(16)
+}
+
+/// parses "8MHz", "32.768kHz", "8M" or "8000000"
+pub fn parse_freq(value: &str) -> Result<u32, String> {
+    let lower = value.trim().to_lowercase();
+    let number = lower.trim_end_matches("hz");
+    let (number, unit) = match number.strip_suffix('m') {
+        Some(number) => (number, 1_000_000.0),
+        None => match number.strip_suffix('k') {
+            Some(number) => (number, 1_000.0),
+            None => (number, 1.0),
+        },
+    };
+
+    let hz = number
+        .trim()
+        .parse::<f64>()
+        .map(|n| n * unit)
+        .map_err(|_| format!("invalid frequency \"{value}\", eg: 8MHz"))?;
+    if hz <= 0.0 || hz.fract() != 0.0 || hz > u32::MAX as f64 {
+        return Err(format!("invalid frequency \"{value}\", eg: 8MHz"));
+    }
+
+    Ok(hz as u32)
+}
+
+/// formats a frequency the way [`parse_freq`] reads it, eg: "168MHz"
+pub fn format_freq(hz: u32) -> String {
+    match (hz % 1_000_000, hz % 1_000) {
+        (0, _) => format!("{}MHz", hz / 1_000_000),
+        (_, 0) => format!("{}kHz", hz / 1_000),
+        _ => format!("{hz}Hz"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::data::DATA;
+
+    fn plan(mcu: &str, hse: Option<u32>, sysclk: Sysclk) -> Result<RccPlan, String> {
+        DATA.clocks(mcu).unwrap().plan(hse, sysclk)
+    }
+
+    #[test]
+    fn runs_an_f401_from_the_hsi_with_a_usb_clock() {
+        let plan = plan("stm32f401cc", None, Sysclk::Max).unwrap();
+        assert_eq!(plan.sysclk, 84_000_000);
+        assert_eq!((plan.prediv, plan.mul, plan.div), (8, 168, 4));
+        assert_eq!(plan.divq, Some(7));
+        assert_eq!((plan.apb1, plan.apb2), (2, 1));
+    }
+
+    #[test]
+    fn boosts_a_g474_from_the_hsi() {
+        let plan = plan("stm32g474re", None, Sysclk::Max).unwrap();
+        assert_eq!(plan.sysclk, 170_000_000);
+        assert_eq!((plan.prediv, plan.mul, plan.div), (4, 85, 2));
+        assert!(plan.render(false).contains("config.rcc.boost = true;"));
+    }
+
+    #[test]
+    fn runs_an_f103_from_an_8mhz_hse() {
+        let plan = plan("stm32f103c8", Some(8_000_000), Sysclk::Max).unwrap();
+        assert_eq!(plan.sysclk, 72_000_000);
+        assert_eq!((plan.prediv, plan.mul), (1, 9));
+        assert_eq!((plan.apb1, plan.apb2), (2, 1));
+    }
+
+    #[test]
+    fn keeps_connectivity_lines_to_their_own_pll() {
+        let plan = plan("stm32f107vc", Some(25_000_000), Sysclk::Max).unwrap();
+        assert_eq!(plan.sysclk, 56_250_000);
+        assert_eq!((plan.prediv, plan.mul), (4, 9));
+        assert!(self::plan("stm32f105rc", Some(2_000_000), Sysclk::Max).is_err());
+    }
+
+    #[test]
+    fn rejects_an_hse_out_of_range() {
+        let err = plan("stm32f103c8", Some(32_000_000), Sysclk::Max).err();
+        assert_eq!(
+            err.as_deref(),
+            Some("the HSE must be between 4MHz and 16MHz")
+        );
+    }
+
+    #[test]
+    fn rejects_a_sysclk_above_the_maximum() {
+        let err = plan("stm32f401cc", None, Sysclk::Hz(100_000_000)).err();
+        assert_eq!(err.as_deref(), Some("100MHz is above the maximum of 84MHz"));
+    }
+
+    #[test]
+    fn rejects_a_sysclk_the_pll_cannot_reach() {
+        let err = plan("stm32f103c8", Some(8_000_000), Sysclk::Hz(71_000_000)).err();
+        assert_eq!(
+            err.as_deref(),
+            Some("no PLL setting reaches exactly 71MHz from the 8MHz HSE")
+        );
+    }
+}
@@ -1,7 +1,10 @@
+use clap::{builder::PossibleValue, ValueEnum};
 use serde::{Deserialize, Serialize};
 
 use crate::commands::create::Vendor;
 
+use super::{boards::Board, clocks::Clocks};
+
 lazy_static::lazy_static! {
     pub static ref DATA: Data = serde_json::from_str(include_str!("../../data/mcu_list.json")).unwrap();
 }
@@ -10,6 +13,10 @@ lazy_static::lazy_static! {
 pub struct Data {
     vendors: Vec<DataVendor>,
     flavors: Vec<Flavor>,
+    memory: Vec<Memory>,
+    time: Vec<Time>,
+    clocks: Vec<Clocks>,
+    boards: Vec<Board>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,6 +29,126 @@ pub struct DataVendor {
 pub struct Flavor {
     regex: String,
     target: String,
+    /// `arch-*` feature of embassy-executor
+    arch: String,
+    /// interrupts no HAL driver needs, free to run an interrupt executor
+    interrupts: Vec<String>,
+}
+
+impl Flavor {
+    pub fn regex(&self) -> &str {
+        &self.regex
+    }
+
+    pub fn arch(&self) -> &str {
+        &self.arch
+    }
+
+    pub fn interrupts(&self) -> &[String] {
+        &self.interrupts
+    }
+}
+
+/// flash and RAM sizes in KiB, flash is decoded from the part number when missing
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Memory {
+    regex: String,
+    flash: Option<u32>,
+    ram: u32,
+}
+
+/// time drivers of the HAL, the first one is the default, and the tick rate of embassy-time
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Time {
+    regex: String,
+    drivers: Vec<String>,
+    tick_hz: u64,
+    /// the driver only runs at `tick_hz`
+    #[serde(default)]
+    fixed_tick: bool,
+}
+
+impl Time {
+    pub fn drivers(&self) -> &[String] {
+        &self.drivers
+    }
+
+    pub fn default_driver(&self) -> &str {
+        &self.drivers[0]
+    }
+
+    pub fn tick_hz(&self) -> u64 {
+        self.tick_hz
+    }
+
+    /// checks a tick rate against the driver and the `tick-hz-*` features of embassy-time
+    pub fn validate_tick_hz(&self, mcu: &str, hz: u64) -> Result<(), String> {
+        if self.fixed_tick && hz != self.tick_hz {
+            return Err(format!(
+                "the {mcu} time driver only runs at {} Hz",
+                self.tick_hz
+            ));
+        }
+        if !tick_rates().contains(&hz) {
+            return Err(format!(
+                "embassy-time has no tick-hz feature for {hz} Hz, use a power of 2 or 10, \
+                 a power of 2 times 1000 or 10000, or a MHz rate such as 16000000"
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// `tick-hz-*` feature for a tick rate, eg: "tick-hz-32_768"
+pub fn tick_feature(hz: u64) -> String {
+    format!("tick-hz-{}", group_digits(hz))
+}
+
+/// digits in groups of three, eg: "8_000_000"
+pub fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push('_');
+        }
+        grouped.push(c);
+    }
+
+    grouped
+}
+
+/// task arena sizes embassy-executor has a `task-arena-size-*` feature for
+fn task_arena_sizes() -> Vec<u32> {
+    let mut sizes = vec![];
+    for size in (6..=20).map(|i| 2u32.pow(i)) {
+        sizes.push(size);
+        if (256..1 << 20).contains(&size) {
+            sizes.push(size / 4 * 5);
+        }
+        if (128..1 << 20).contains(&size) {
+            sizes.push(size / 2 * 3);
+        }
+    }
+    sizes
+}
+
+/// tick rates embassy-time has a feature for, as its gen_tick.py lists them
+fn tick_rates() -> Vec<u64> {
+    let mut rates = vec![];
+    rates.extend((0..10).map(|i| 10u64.pow(i)));
+    rates.extend((1..25).map(|i| 2u64.pow(i)));
+    rates.extend((1..20).map(|i| 2u64.pow(i) * 1_000));
+    rates.extend((1..20).map(|i| 2u64.pow(i) * 10_000));
+    for i in 1..10 {
+        rates.push(2u64.pow(i) * 1_000_000);
+        rates.push(2u64.pow(i) * 9 / 8 * 1_000_000);
+        rates.push(2u64.pow(i) * 3 / 2 * 1_000_000);
+    }
+    rates.extend((1..30).map(|i| 10 * i * 1_000_000));
+    rates.extend((15..50).map(|i| 20 * i * 1_000_000));
+    rates
 }
 
 // const DATA: Data = serde_json::from_str(include!("../data/mcu_list.json"));
@@ -46,30 +173,340 @@ impl Data {
     }
 
     pub fn target(&self, mcu: &str) -> anyhow::Result<String> {
+        Ok(self.flavor(mcu)?.target.clone())
+    }
+
+    pub fn flavor(&self, mcu: &str) -> anyhow::Result<&Flavor> {
         for flavor in &self.flavors {
             let re = regex::Regex::new(&flavor.regex)?;
             if re.is_match(mcu) {
-                return Ok(flavor.target.clone());
+                return Ok(flavor);
             }
         }
 
         anyhow::bail!("No target found for MCU: {}", mcu);
     }
 
+    /// chip family of an MCU, eg: "stm32f4" for "stm32f446re"
+    pub fn family(&self, mcu: &str) -> anyhow::Result<String> {
+        Ok(self.flavor(mcu)?.regex.trim_end_matches(".*").to_owned())
+    }
+
+    /// list of supported targets, with the flavors that use them
+    pub fn target_list(&self) -> Vec<(String, Vec<String>)> {
+        let mut targets: Vec<(String, Vec<String>)> = vec![];
+        for flavor in &self.flavors {
+            match targets.iter_mut().find(|(t, _)| *t == flavor.target) {
+                Some((_, regexes)) => regexes.push(flavor.regex.clone()),
+                None => targets.push((flavor.target.clone(), vec![flavor.regex.clone()])),
+            }
+        }
+        targets
+    }
+
+    pub fn vendor_of(&self, mcu: &str) -> Option<Vendor> {
+        self.vendors
+            .iter()
+            .find(|v| v.mcu_list.iter().any(|m| m == mcu))
+            .and_then(|v| Vendor::from_str(&v.name, true).ok())
+    }
+
+    /// flash and RAM sizes in KiB, when known
+    pub fn memory(&self, mcu: &str) -> (Option<u32>, Option<u32>) {
+        let memory = self.memory.iter().find(|m| {
+            regex::Regex::new(&format!("^{}$", m.regex))
+                .map(|re| re.is_match(mcu))
+                .unwrap_or(false)
+        });
+
+        let flash = memory
+            .and_then(|m| m.flash)
+            .or_else(|| stm32_flash_size(mcu));
+        (flash, memory.map(|m| m.ram))
+    }
+
+    /// checks a task arena size against the features of embassy-executor and the RAM of the MCU
+    pub fn validate_task_arena_size(&self, mcu: &str, size: u32) -> Result<(), String> {
+        if !task_arena_sizes().contains(&size) {
+            let mut sizes = task_arena_sizes();
+            sizes.sort();
+            let sizes = sizes.iter().map(u32::to_string).collect::<Vec<_>>();
+            return Err(format!(
+                "embassy-executor has no task-arena-size feature for {size} bytes, expected one of: {}",
+                sizes.join(", ")
+            ));
+        }
+        if let (_, Some(ram)) = self.memory(mcu) {
+            if size > ram * 1024 {
+                return Err(format!(
+                    "{size} bytes do not fit in the {ram} KiB of RAM of {mcu}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn time(&self, mcu: &str) -> anyhow::Result<&Time> {
+        for time in &self.time {
+            let re = regex::Regex::new(&format!("^{}$", time.regex))?;
+            if re.is_match(mcu) {
+                return Ok(time);
+            }
+        }
+
+        anyhow::bail!("No time driver found for MCU: {}", mcu);
+    }
+
+    pub fn boards(&self) -> &[Board] {
+        &self.boards
+    }
+
+    pub fn board(&self, name: &str) -> Option<&Board> {
+        self.boards
+            .iter()
+            .find(|board| board.name().eq_ignore_ascii_case(name))
+    }
+
+    /// PLL limits of an MCU, `None` when the family has no RCC support yet
+    pub fn clocks(&self, mcu: &str) -> Option<&Clocks> {
+        self.clocks.iter().find(|clocks| {
+            regex::Regex::new(&format!("^{}$", clocks.regex())).is_ok_and(|re| re.is_match(mcu))
+        })
+    }
+
+    /// product line of an MCU, eg: "stm32f446" for "stm32f446re" or "nrf5340" for "nrf5340-app-s"
+    pub fn line(&self, mcu: &str) -> String {
+        match mcu.starts_with("stm32") {
+            true => mcu.chars().take(9).collect(),
+            false => mcu.split('-').next().unwrap_or(mcu).to_owned(),
+        }
+    }
+
+    /// maps a marketing part number to its embassy name, eg: "STM32F446RET6" to "stm32f446re"
+    pub fn normalize(&self, part: &str) -> Option<String> {
+        let mut part = part.trim().to_lowercase();
+        while !part.is_empty() {
+            if self.vendors.iter().any(|v| v.mcu_list.contains(&part)) {
+                return Some(part);
+            }
+            part.pop();
+        }
+
+        None
+    }
+
+    /// MCUs matching a partial or marketing part number, best matches first
+    pub fn search(&self, vendor: Option<Vendor>, query: &str) -> Vec<String> {
+        if let Some(mcu) = self.normalize(query) {
+            return vec![mcu];
+        }
+
+        let query = query.trim().to_lowercase();
+        let vendor: Option<String> = vendor.map(Into::into);
+        let mut matches = self
+            .vendors
+            .iter()
+            .filter(|v| vendor.as_ref().is_none_or(|name| *name == v.name))
+            .flat_map(|v| &v.mcu_list)
+            .filter_map(|mcu| Some((fuzzy_score(&query, mcu)?, mcu)))
+            .collect::<Vec<_>>();
+        matches.sort();
+
+        matches.into_iter().map(|(_, mcu)| mcu.clone()).collect()
+    }
+
+    /// vendor of a partial or marketing part number, if all its matches (or the closest MCUs
+    /// when nothing matches) share the same vendor
+    pub fn infer_vendor(&self, query: &str) -> Option<Vendor> {
+        let mut candidates = self.search(None, query);
+        if candidates.is_empty() {
+            candidates = self.suggestions(query);
+        }
+
+        let mut vendors = candidates
+            .into_iter()
+            .filter_map(|mcu| self.vendor_of(&mcu));
+        let vendor = vendors.next()?;
+        vendors.all(|v| v == vendor).then_some(vendor)
+    }
+
+    /// closest known MCU names by edit distance, plus the other parts of the same line
+    pub fn suggestions(&self, mcu: &str) -> Vec<String> {
+        let mcu = mcu.to_lowercase();
+        let line = self.line(&mcu);
+        let max_distance = (mcu.len() / 3).max(2);
+
+        let mut scored = self
+            .vendors
+            .iter()
+            .flat_map(|v| &v.mcu_list)
+            .map(|candidate| {
+                let score = match candidate.contains(&mcu) {
+                    true => 0,
+                    false => edit_distance(&mcu, candidate),
+                };
+                (score, candidate)
+            })
+            .filter(|(score, candidate)| *score <= max_distance || self.line(candidate) == line)
+            .collect::<Vec<_>>();
+        scored.sort();
+
+        scored
+            .into_iter()
+            .take(5)
+            .map(|(_, candidate)| candidate.clone())
+            .collect()
+    }
+
     pub fn validate(&self, vendor: Vendor, mcu: &str) -> anyhow::Result<()> {
         if !self.vendor_list().contains(&vendor.into()) {
-            anyhow::bail!("Invalid vendor: {:?}", vendor);
+            return Err(DataError::InvalidVendor(vendor).into());
         }
 
         if !self.mcu_list(vendor)?.contains(&mcu.to_owned()) {
-            anyhow::bail!("Invalid MCU: {}", mcu);
+            return Err(match self.vendor_of(mcu) {
+                Some(actual) => DataError::VendorMismatch {
+                    mcu: mcu.to_owned(),
+                    vendor,
+                    actual,
+                },
+                None => DataError::InvalidMcu {
+                    mcu: mcu.to_owned(),
+                    suggestions: self.suggestions(mcu),
+                },
+            }
+            .into());
         }
 
         Ok(())
     }
 }
 
+/// vendor and MCU validation errors, rendered with suggestions by the CLI
+#[derive(Debug)]
+pub enum DataError {
+    InvalidVendor(Vendor),
+    InvalidMcu {
+        mcu: String,
+        suggestions: Vec<String>,
+    },
+    VendorMismatch {
+        mcu: String,
+        vendor: Vendor,
+        actual: Vendor,
+    },
+}
+
+impl std::fmt::Display for DataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataError::InvalidVendor(vendor) => write!(f, "Invalid vendor: {vendor:?}"),
+            DataError::InvalidMcu { mcu, .. } => write!(f, "Invalid MCU: {mcu}"),
+            DataError::VendorMismatch {
+                mcu,
+                vendor,
+                actual,
+            } => write!(
+                f,
+                "MCU {mcu} is made by {}, not {}",
+                String::from(*actual),
+                String::from(*vendor)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DataError {}
+
+impl DataError {
+    pub fn render(&self) -> String {
+        let mut rendered = format!("error: {self}");
+        match self {
+            DataError::InvalidMcu { suggestions, .. } if !suggestions.is_empty() => {
+                rendered.push_str("\n\ndid you mean one of these?");
+                for suggestion in suggestions {
+                    rendered.push_str(&format!("\n    {suggestion}"));
+                }
+            }
+            DataError::InvalidMcu { .. } => {
+                rendered.push_str("\n\nrun `embassy-cli list mcus` to see the supported MCUs")
+            }
+            DataError::VendorMismatch { actual, .. } => rendered.push_str(&format!(
+                "\n\nuse `--vendor {}` or leave it out to infer it from the MCU",
+                String::from(*actual)
+            )),
+            DataError::InvalidVendor(_) => {}
+        }
+        rendered
+    }
+}
+
+/// decodes the flash size code of an STM32 part number, eg: "e" in "stm32f446re" is 512 KiB
+fn stm32_flash_size(mcu: &str) -> Option<u32> {
+    if !mcu.starts_with("stm32") {
+        return None;
+    }
+
+    let part = mcu.split('-').next()?;
+    Some(match part.chars().last()? {
+        '3' => 8,
+        '4' => 16,
+        '6' => 32,
+        '8' => 64,
+        'b' => 128,
+        'z' => 192,
+        'c' => 256,
+        'd' => 384,
+        'e' => 512,
+        'f' => 768,
+        'g' => 1024,
+        'h' => 1536,
+        'i' => 2048,
+        'j' => 4096,
+        _ => return None,
+    })
+}
+
+/// lower is better, substring matches rank before scattered subsequence matches
+fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    if let Some(position) = candidate.find(query) {
+        return Some(position);
+    }
+
+    let mut gaps = 0;
+    let mut chars = candidate.chars();
+    for q in query.chars() {
+        loop {
+            match chars.next() {
+                Some(c) if c == q => break,
+                Some(_) => gaps += 1,
+                None => return None,
+            }
+        }
+    }
+
+    Some(candidate.len() + gaps)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 /// list of supported embassy crates
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum EmbassyCrates {
     Executor,
     Futures,
@@ -84,7 +521,38 @@ pub enum EmbassyCrates {
 
 impl From<EmbassyCrates> for String {
     fn from(val: EmbassyCrates) -> Self {
-        match val {
+        val.name().to_owned()
+    }
+}
+
+impl ValueEnum for EmbassyCrates {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Self::Executor,
+            Self::Futures,
+            Self::Lora,
+            Self::Nrf,
+            Self::Rp,
+            Self::Stm32Wpan,
+            Self::Stm32,
+            Self::Sync,
+            Self::Time,
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        let name = self.name();
+        Some(PossibleValue::new(name).alias(name.trim_start_matches("embassy-")))
+    }
+}
+
+impl EmbassyCrates {
+    pub fn default_crates() -> Vec<Self> {
+        [Self::Executor, Self::Time, Self::Sync, Self::Futures].into()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
             EmbassyCrates::Executor => "embassy-executor",
             EmbassyCrates::Futures => "embassy-futures",
             EmbassyCrates::Lora => "embassy-lora",
@@ -95,12 +563,5 @@ impl From<EmbassyCrates> for String {
             EmbassyCrates::Sync => "embassy-sync",
             EmbassyCrates::Time => "embassy-time",
         }
-        .to_owned()
-    }
-}
-
-impl EmbassyCrates {
-    pub fn default_crates() -> Vec<Self> {
-        [Self::Executor, Self::Time, Self::Sync, Self::Futures].into()
     }
 }
@@ -0,0 +1,320 @@
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+/// bus trait of embedded-hal-async the driver is generic over
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Bus {
+    /// Addressed on a shared I2C bus
+    #[default]
+    I2c,
+    /// An SPI device with its own chip select
+    Spi,
+}
+
+/// an async driver crate for a device on an I2C or SPI bus
+pub struct Driver {
+    /// crate name, eg: "bme280"
+    pub name: String,
+    pub bus: Bus,
+    /// the crate is a member of the project's workspace
+    pub workspace: bool,
+}
+
+/// crates the generated driver depends on
+const DEPENDENCIES: [&str; 4] = [
+    "embedded-hal-async",
+    "defmt",
+    "embassy-futures",
+    "embedded-hal-mock",
+];
+
+impl Driver {
+    /// `taken` are the crates the project already depends on
+    pub fn validate(&self, taken: &[String]) -> Result<(), String> {
+        let valid = self.name.starts_with(|c: char| c.is_ascii_lowercase())
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!(
+                "\"{}\" is not a lowercase crate name, eg: bme280",
+                self.name
+            ));
+        }
+        // cargo does not tell `-` and `_` apart in crate names
+        let same = |other: &str| other.replace('_', "-") == self.name.replace('_', "-");
+        if DEPENDENCIES.into_iter().any(same) {
+            return Err(format!(
+                "\"{}\" is a dependency of the driver itself, pick another name",
+                self.name
+            ));
+        }
+        if let Some(other) = taken.iter().find(|other| same(other)) {
+            return Err(format!(
+                "the project already depends on \"{other}\", pick another name"
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// name of the driver struct, eg: "Bme280" for bme280
+    pub fn type_name(&self) -> String {
+        self.name
+            .split(['-', '_'])
+            .map(|part| {
+                let mut chars = part.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                    None => String::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// files of the crate, relative to its directory
+    pub fn files(&self) -> Vec<(PathBuf, String)> {
+        let mut files = vec![
+            ("Cargo.toml".into(), self.cargo_toml()),
+            (PathBuf::from("src").join("lib.rs"), self.lib_rs()),
+            (
+                PathBuf::from("src").join("registers.rs"),
+                self.registers_rs(),
+            ),
+        ];
+        // a workspace ignores its members' targets already
+        if !self.workspace {
+            files.push((".gitignore".into(), "/target\n".into()));
+        }
+        files
+    }
+
+    fn cargo_toml(&self) -> String {
+        let name = &self.name;
+        format!(
+            r#"[package]
+name = "{name}"
+version = "0.1.0"
+edition = "2021"
+
+[features]
+defmt = ["dep:defmt", "embedded-hal-async/defmt-03"]
+
+[dependencies]
+embedded-hal-async = "1.0.0"
+defmt = {{ version = "0.3", optional = true }}
+
+[dev-dependencies]
+embassy-futures = "0.1.1"
+embedded-hal-mock = {{ version = "0.11.1", default-features = false, features = ["eh1", "embedded-hal-async"] }}
+"#
+        )
+    }
+
+    fn registers_rs(&self) -> String {
+        let read = match self.bus {
+            Bus::I2c => "",
+            Bus::Spi => {
+                "\n/// set in the address byte to read a register, cleared to write it\npub const READ: u8 = 0x80;\n"
+            }
+        };
+
+        format!(
+            r#"//! register map of the device, fill in the addresses from its datasheet
+
+/// what [`Register::WhoAmI`] holds on the device
+pub const WHO_AM_I: u8 = 0x42;
+{read}
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Register {{
+    /// identifies the device
+    WhoAmI = 0x0f,
+    /// configuration of the device
+    Ctrl = 0x20,
+    /// first byte of a measurement
+    Data = 0x28,
+}}
+
+impl Register {{
+    pub fn addr(self) -> u8 {{
+        self as u8
+    }}
+}}
+"#
+        )
+    }
+
+    fn lib_rs(&self) -> String {
+        let ty = self.type_name();
+        let (bus_trait, bus, field, address_const, new, read, write) = match self.bus {
+            Bus::I2c => (
+                "i2c::I2c",
+                "I2C",
+                "i2c: I2C,\n    address: u8,",
+                "\n/// default 7-bit I2C address of the device\npub const ADDRESS: u8 = 0x42;\n",
+                "pub fn new(i2c: I2C, address: u8) -> Self {\n        Self { i2c, address }\n    }",
+                "let mut value = [0];\n        self.i2c\n            .write_read(self.address, &[register.addr()], &mut value)\n            .await\n            .map_err(Error::Bus)?;\n        Ok(value[0])",
+                "self.i2c\n            .write(self.address, &[register.addr(), value])\n            .await\n            .map_err(Error::Bus)",
+            ),
+            Bus::Spi => (
+                "spi::{Operation, SpiDevice}",
+                "SPI",
+                "spi: SPI,",
+                "",
+                "pub fn new(spi: SPI) -> Self {\n        Self { spi }\n    }",
+                "let mut value = [0];\n        self.spi\n            .transaction(&mut [\n                Operation::Write(&[register.addr() | READ]),\n                Operation::Read(&mut value),\n            ])\n            .await\n            .map_err(Error::Bus)?;\n        Ok(value[0])",
+                "self.spi\n            .write(&[register.addr() & !READ, value])\n            .await\n            .map_err(Error::Bus)",
+            ),
+        };
+        let bound = match self.bus {
+            Bus::I2c => "I2c",
+            Bus::Spi => "SpiDevice",
+        };
+        let registers = match self.bus {
+            Bus::I2c => "registers::WHO_AM_I",
+            Bus::Spi => "registers::{READ, WHO_AM_I}",
+        };
+        let release = bus.to_lowercase();
+        let tests = self.tests();
+
+        format!(
+            r#"//! async driver for the {ty}, generic over the embedded-hal-async {bus} traits
+#![cfg_attr(not(test), no_std)]
+
+pub mod registers;
+
+use embedded_hal_async::{bus_trait};
+use {registers};
+
+pub use registers::Register;
+{address_const}
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {{
+    /// the bus failed
+    Bus(E),
+    /// another device answered, with this ID
+    WrongDevice(u8),
+}}
+
+pub struct {ty}<{bus}> {{
+    {field}
+}}
+
+impl<{bus}: {bound}> {ty}<{bus}> {{
+    {new}
+
+    /// checks the device is there and is the right one
+    pub async fn init(&mut self) -> Result<(), Error<{bus}::Error>> {{
+        match self.read_register(Register::WhoAmI).await? {{
+            WHO_AM_I => Ok(()),
+            id => Err(Error::WrongDevice(id)),
+        }}
+    }}
+
+    pub async fn read_register(&mut self, register: Register) -> Result<u8, Error<{bus}::Error>> {{
+        {read}
+    }}
+
+    pub async fn write_register(
+        &mut self,
+        register: Register,
+        value: u8,
+    ) -> Result<(), Error<{bus}::Error>> {{
+        {write}
+    }}
+
+    /// gives the bus back
+    pub fn release(self) -> {bus} {{
+        self.{release}
+    }}
+}}
+
+{tests}
+"#
+        )
+    }
+
+    /// host tests against the mock buses of embedded-hal-mock
+    fn tests(&self) -> String {
+        let ty = self.type_name();
+        let (mock, new) = match self.bus {
+            Bus::I2c => (
+                "i2c::{Mock, Transaction}",
+                "Mock::new(&expectations), ADDRESS",
+            ),
+            Bus::Spi => ("spi::{Mock, Transaction}", "Mock::new(&expectations)"),
+        };
+        // laid out the way rustfmt does, on one line when they fit in its array width
+        let expectations = |transactions: &[&str]| {
+            let mut lines = transactions.to_vec();
+            if self.bus == Bus::Spi {
+                lines.insert(0, "Transaction::transaction_start()");
+                lines.push("Transaction::transaction_end()");
+            }
+            match lines.join(", ") {
+                line if line.len() <= 60 => format!("[{line}]"),
+                _ => format!(
+                    "[\n            {},\n        ]",
+                    lines.join(",\n            ")
+                ),
+            }
+        };
+        let (init, wrong, write) = match self.bus {
+            Bus::I2c => (
+                expectations(&["Transaction::write_read(ADDRESS, vec![0x0f], vec![WHO_AM_I])"]),
+                expectations(&["Transaction::write_read(ADDRESS, vec![0x0f], vec![0x00])"]),
+                expectations(&["Transaction::write(ADDRESS, vec![0x20, 0x01])"]),
+            ),
+            Bus::Spi => (
+                expectations(&[
+                    "Transaction::write_vec(vec![0x8f])",
+                    "Transaction::read_vec(vec![WHO_AM_I])",
+                ]),
+                expectations(&[
+                    "Transaction::write_vec(vec![0x8f])",
+                    "Transaction::read_vec(vec![0x00])",
+                ]),
+                expectations(&["Transaction::write_vec(vec![0x20, 0x01])"]),
+            ),
+        };
+
+        format!(
+            r#"#[cfg(test)]
+mod tests {{
+    use embassy_futures::block_on;
+    use embedded_hal_mock::eh1::{mock};
+
+    use super::*;
+
+    #[test]
+    fn init_finds_the_device() {{
+        let expectations = {init};
+        let mut device = {ty}::new({new});
+        assert_eq!(block_on(device.init()), Ok(()));
+        device.release().done();
+    }}
+
+    #[test]
+    fn init_rejects_another_device() {{
+        let expectations = {wrong};
+        let mut device = {ty}::new({new});
+        assert_eq!(block_on(device.init()), Err(Error::WrongDevice(0x00)));
+        device.release().done();
+    }}
+
+    #[test]
+    fn writes_a_register() {{
+        let expectations = {write};
+        let mut device = {ty}::new({new});
+        let written = block_on(device.write_register(Register::Ctrl, 0x01));
+        assert_eq!(written, Ok(()));
+        device.release().done();
+    }}
+}}"#
+        )
+    }
+}
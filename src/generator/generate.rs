@@ -1,26 +1,161 @@
-use std::io::Write;
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-use crate::commands::create::Vendor;
+use crate::{
+    commands::create::{Editor, Executor, LogLevel, Logging, Panic, Runner, Vendor},
+    error::ValidationError,
+    git::Git,
+};
 
-use super::templates::TemplateBuilder;
+use super::{
+    data::EmbassyCrates,
+    peripherals::Peripheral,
+    tasks::Task,
+    templates::{project_crates, TemplateBuilder},
+};
 
 pub struct GeneratorConfig {
     pub name: String,
     pub vendor: Vendor,
     pub mcu: String,
+    /// development board the example in main.rs is written for
+    pub board: Option<String>,
     pub target: String,
     pub no_pin: bool,
     pub workspace: bool,
+    pub runner: Runner,
+    pub upstream: String,
+    pub logging: Logging,
+    pub log_level: LogLevel,
+    pub panic: Panic,
+    pub editor: Editor,
+    pub license: Option<String>,
+    pub crates: Vec<EmbassyCrates>,
+    pub features: Vec<String>,
+    pub template: Option<PathBuf>,
+    /// `time-driver-*` feature of the HAL
+    pub time_driver: String,
+    pub tick_hz: u64,
+    /// `arch-*` feature of embassy-executor
+    pub arch: String,
+    pub executor: Executor,
+    /// interrupt of the interrupt executor, set with [`Executor::Interrupt`]
+    pub executor_irq: Option<String>,
+    pub task_arena_size: Option<u32>,
+    /// HSE crystal in Hz, `None` runs from the HSI
+    pub hse: Option<u32>,
+    /// system clock in Hz the PLL is set up for, `None` keeps the HAL defaults
+    pub sysclk: Option<u32>,
+    /// UART, I2C and SPI drivers with an example task each, checked against the chip data
+    pub peripherals: Vec<Peripheral>,
+    /// modules added with `generate task`, spawned from main.rs
+    pub tasks: Vec<Task>,
+    /// `stable`, `nightly` or a toolchain channel, `None` follows the embassy revision
+    pub toolchain: Option<String>,
+    /// embassy commit the project is pinned to, filled in by [`prepare`]
+    pub rev: Option<String>,
+    /// toolchain channel the project builds with, filled in by [`prepare`]
+    pub channel: String,
+    /// versions of the embassy crates at that revision, filled in by [`prepare`]
+    pub versions: BTreeMap<String, String>,
 }
 
-pub async fn create(cfg: GeneratorConfig) -> anyhow::Result<()> {
-    let path = TemplateBuilder::new(cfg).await?.build()?;
+impl GeneratorConfig {
+    /// nightly-only features and crate attributes are used
+    pub fn nightly(&self) -> bool {
+        is_nightly(&self.channel)
+    }
+}
+
+/// resolves the embassy revision, toolchain and crate versions, then renders every file
+/// without touching the disk
+pub async fn prepare(mut cfg: GeneratorConfig) -> anyhow::Result<TemplateBuilder> {
+    if !cfg.no_pin && cfg.rev.is_none() {
+        cfg.rev = Some(Git::get_latest_commit().await?);
+    }
+
+    // a lock file may already have resolved everything
+    let reference = cfg.rev.clone().unwrap_or("main".into());
+    if cfg.channel.is_empty() {
+        cfg.channel = resolve_channel(cfg.toolchain.as_deref(), &reference).await?;
+    }
+    for (name, _) in project_crates(&cfg) {
+        if let Entry::Vacant(entry) = cfg.versions.entry(name) {
+            let version = Git::get_crate_version(entry.key(), &reference).await?;
+            entry.insert(version);
+        }
+    }
+
+    TemplateBuilder::new(cfg).await
+}
+
+/// channel for the requested toolchain at an embassy revision, stable when the revision builds
+/// on it and nothing else was asked for
+pub async fn resolve_channel(toolchain: Option<&str>, reference: &str) -> anyhow::Result<String> {
+    let upstream = Git::get_toolchain_channel(reference).await?;
+    Ok(match toolchain {
+        None => upstream,
+        Some("stable") if is_nightly(&upstream) => anyhow::bail!(ValidationError(format!(
+            "embassy {reference} needs a nightly toolchain ({upstream}), pass --toolchain nightly"
+        ))),
+        Some("stable") => upstream,
+        Some("nightly") if is_nightly(&upstream) => upstream,
+        Some("nightly") => match Git::get_nightly_toolchain_channel(reference).await {
+            Ok(channel) => channel,
+            // only revisions that build on stable keep a nightly toolchain file
+            Err(e) if is_not_found(&e) => {
+                eprintln!(
+                    "warning: embassy {reference} does not pin a nightly toolchain, \
+                     using the latest nightly"
+                );
+                "nightly".into()
+            }
+            Err(e) => return Err(e),
+        },
+        Some(channel) => channel.to_owned(),
+    })
+}
+
+pub fn is_nightly(channel: &str) -> bool {
+    channel.starts_with("nightly")
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|status| status == reqwest::StatusCode::NOT_FOUND)
+}
+
+/// writes the project into `parent` and formats it, returns warnings about anything that did not
+/// go as planned
+pub fn write(builder: &TemplateBuilder, parent: &Path) -> anyhow::Result<Vec<String>> {
+    let mut warnings = vec![];
+    if builder.cfg().rev.is_none() {
+        warnings.push(
+            "embassy is not pinned to a commit, the project may break when upstream changes".into(),
+        );
+    }
+    for file in builder.overridden_files() {
+        warnings.push(format!(
+            "{} from the template replaced the generated one",
+            file.display()
+        ));
+    }
+
+    let path = builder.build(parent)?;
 
     // run cargo fmt
-    std::process::Command::new("cargo")
+    let formatted = std::process::Command::new("cargo")
         .arg("fmt")
         .current_dir(&path)
-        .output()?;
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !formatted {
+        warnings.push("cargo fmt failed, the generated code is not formatted".into());
+    }
 
     // format toml files
     let toml_paths = [path.join("Cargo.toml"), path.join("./.cargo/config.toml")];
@@ -35,7 +170,9 @@ pub async fn create(cfg: GeneratorConfig) -> anyhow::Result<()> {
         file.write_all(as_string.as_bytes())?;
     }
 
-    Ok(())
+    builder.write_lock(&path)?;
+
+    Ok(warnings)
 }
 
 fn remove_triple_newlines(string: &str) -> String {
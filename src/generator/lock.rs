@@ -0,0 +1,199 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::ValidationError,
+    preset::{relative_path, Preset},
+};
+
+use super::{
+    clocks::parse_freq,
+    data::{EmbassyCrates, DATA},
+    generate::GeneratorConfig,
+};
+
+/// name of the lock file at the root of generated projects
+pub const LOCK_FILE: &str = "embassy-cli.lock";
+
+const LOCK_VERSION: u32 = 1;
+
+/// everything needed to generate a project again, and what the generated files looked like
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Lock {
+    pub version: u32,
+    /// version of embassy-cli, and so of the templates, that generated the project
+    pub embassy_cli: String,
+    pub name: String,
+    pub inputs: Preset,
+    pub resolved: Resolved,
+    /// sha256 of every generated file, relative to the project root
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Resolved {
+    pub rev: Option<String>,
+    pub toolchain: String,
+    pub crates: BTreeMap<String, String>,
+}
+
+impl Lock {
+    /// records `cfg` and hashes `files`, which are relative to `root`
+    pub fn new(cfg: &GeneratorConfig, root: &Path, files: &[PathBuf]) -> anyhow::Result<Self> {
+        let mut hashes = BTreeMap::new();
+        for file in files {
+            hashes.insert(file.clone(), hash_file(&root.join(file))?);
+        }
+
+        Ok(Self {
+            version: LOCK_VERSION,
+            embassy_cli: env!("CARGO_PKG_VERSION").into(),
+            name: cfg.name.clone(),
+            // relative to the project, so it can be moved along with the template
+            inputs: Preset {
+                template: cfg.template.as_ref().map(|t| relative_path(t, root)),
+                ..Preset::from(cfg)
+            },
+            resolved: Resolved {
+                rev: cfg.rev.clone(),
+                toolchain: cfg.channel.clone(),
+                crates: cfg.versions.clone(),
+            },
+            files: hashes,
+        })
+    }
+
+    pub fn read(root: &Path) -> anyhow::Result<Self> {
+        let path = root.join(LOCK_FILE);
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            anyhow!(
+                "Could not read {}, was the project generated by embassy-cli? {e}",
+                path.display()
+            )
+        })?;
+        let lock: Self = toml::from_str(&content)
+            .map_err(|e| ValidationError(format!("Invalid {}: {e}", path.display())))?;
+        if lock.version > LOCK_VERSION {
+            anyhow::bail!(ValidationError(format!(
+                "{} was written by a newer embassy-cli ({}), please update",
+                path.display(),
+                lock.embassy_cli
+            )));
+        }
+
+        Ok(lock)
+    }
+
+    /// the lock of a project, `None` when the project was not generated by embassy-cli
+    pub fn read_if_exists(root: &Path) -> anyhow::Result<Option<Self>> {
+        match root.join(LOCK_FILE).exists() {
+            true => Self::read(root).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// whether `file`, relative to `root`, is still what was generated
+    pub fn is_generated(&self, root: &Path, file: &Path) -> bool {
+        self.files
+            .get(file)
+            .is_some_and(|hash| hash_file(&root.join(file)).is_ok_and(|current| current == *hash))
+    }
+
+    /// records what `file` was rewritten to, so it still counts as generated
+    pub fn rehash(&mut self, root: &Path, file: &Path) -> anyhow::Result<()> {
+        self.files
+            .insert(file.to_owned(), hash_file(&root.join(file))?);
+        Ok(())
+    }
+
+    pub fn write(&self, root: &Path) -> anyhow::Result<()> {
+        let content = format!(
+            "# written by embassy-cli, `embassy-cli regen` generates the project again from it\n{}",
+            toml::to_string(self)?
+        );
+        std::fs::write(root.join(LOCK_FILE), content)?;
+        Ok(())
+    }
+
+    /// the generator config that produced the project at `root`, nothing is left to resolve
+    pub fn config(&self, root: &Path) -> anyhow::Result<GeneratorConfig> {
+        let inputs = &self.inputs;
+        let config = inputs.config();
+        let vendor = config
+            .vendor()?
+            .ok_or(ValidationError("No vendor in the lock file".into()))?;
+        let mcu = inputs
+            .mcu
+            .clone()
+            .ok_or(ValidationError("No MCU in the lock file".into()))?;
+        let crates = inputs
+            .crates
+            .iter()
+            .map(|name| {
+                EmbassyCrates::from_str(name, true)
+                    .map_err(|_| anyhow!(ValidationError(format!("Unknown crate \"{name}\""))))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let time = DATA.time(&mcu)?;
+        let arch = DATA.flavor(&mcu)?.arch().to_owned();
+
+        Ok(GeneratorConfig {
+            name: self.name.clone(),
+            vendor,
+            target: DATA.target(&mcu)?,
+            mcu,
+            board: inputs.board.clone(),
+            no_pin: !config.pin.unwrap_or(true),
+            workspace: config.workspace.unwrap_or_default(),
+            runner: config.runner()?,
+            logging: config.logging()?,
+            log_level: config.log_level()?,
+            panic: config.panic()?,
+            editor: config.editor()?,
+            upstream: config.upstream.clone().unwrap_or_default(),
+            license: config.license.clone(),
+            crates,
+            features: inputs.features.clone(),
+            template: inputs.template.as_ref().map(|t| root.join(t)),
+            time_driver: inputs
+                .time_driver
+                .clone()
+                .unwrap_or(time.default_driver().into()),
+            tick_hz: inputs.tick_hz.unwrap_or(time.tick_hz()),
+            arch,
+            executor: config.executor()?,
+            executor_irq: inputs.executor_irq.clone(),
+            task_arena_size: inputs.task_arena_size,
+            hse: freq(inputs.hse.as_deref(), "hse")?,
+            sysclk: freq(inputs.sysclk.as_deref(), "sysclk")?,
+            peripherals: inputs.peripherals.clone(),
+            tasks: inputs.tasks.clone(),
+            toolchain: config.toolchain.clone(),
+            rev: self.resolved.rev.clone(),
+            channel: self.resolved.toolchain.clone(),
+            versions: self.resolved.crates.clone(),
+        })
+    }
+}
+
+fn freq(value: Option<&str>, key: &str) -> anyhow::Result<Option<u32>> {
+    value.map(parse_freq).transpose().map_err(|e| {
+        anyhow!(ValidationError(format!(
+            "Invalid {key} in the lock file: {e}"
+        )))
+    })
+}
+
+pub fn hash_file(path: &Path) -> anyhow::Result<String> {
+    let digest = Sha256::digest(std::fs::read(path)?);
+    Ok(digest.iter().map(|b| format!("{b:02x}")).collect())
+}
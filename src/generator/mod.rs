@@ -1,3 +1,9 @@
+pub mod boards;
+pub mod clocks;
 pub mod data;
+pub mod drivers;
 pub mod generate;
+pub mod lock;
+pub mod peripherals;
+pub mod tasks;
 pub mod templates;
@@ -0,0 +1,926 @@
+use std::{collections::BTreeMap, fmt, path::Path, str::FromStr};
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::create::{Logging, Vendor},
+    config::name_of,
+    error::ValidationError,
+};
+
+use super::{boards::Board, templates};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PeripheralKind {
+    Uart,
+    I2c,
+    Spi,
+}
+
+/// a peripheral driven from main.rs, with the pins, DMA channels and interrupts it uses, keyed
+/// by lowercase signal name, eg: "tx"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Peripheral {
+    pub kind: PeripheralKind,
+    pub instance: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pins: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dma: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub interrupts: BTreeMap<String, String>,
+}
+
+impl PeripheralKind {
+    /// pin signals, in the order `init` takes them
+    fn signals(self) -> &'static [&'static str] {
+        match self {
+            Self::Uart => &["tx", "rx"],
+            Self::I2c => &["scl", "sda"],
+            Self::Spi => &["sck", "mosi", "miso"],
+        }
+    }
+
+    /// DMA signals the async driver needs, the nRF peripherals bring their own DMA
+    fn dma_signals(self, vendor: Vendor) -> &'static [&'static str] {
+        match (vendor, self) {
+            (Vendor::Nrf, _) | (Vendor::Rp, Self::I2c) => &[],
+            _ => &["tx", "rx"],
+        }
+    }
+
+    /// interrupt signals bound to a handler, SPI runs from its DMA interrupts on STM32 and RP
+    fn interrupt_signals(self, vendor: Vendor) -> &'static [&'static str] {
+        match (vendor, self) {
+            (Vendor::St, Self::I2c) => &["ev", "er"],
+            (Vendor::St | Vendor::Rp, Self::Spi) => &[],
+            _ => &["global"],
+        }
+    }
+
+    /// HAL module of the driver
+    fn module(self, vendor: Vendor) -> &'static str {
+        match (vendor, self) {
+            (Vendor::St, Self::Uart) => "usart",
+            (Vendor::Nrf, Self::Uart) => "uarte",
+            (Vendor::Rp, Self::Uart) => "uart",
+            (Vendor::Nrf, Self::I2c) => "twim",
+            (_, Self::I2c) => "i2c",
+            (Vendor::Nrf, Self::Spi) => "spim",
+            (_, Self::Spi) => "spi",
+        }
+    }
+
+    /// driver type in the HAL module
+    fn driver(self, vendor: Vendor) -> &'static str {
+        match (vendor, self) {
+            (Vendor::Nrf, Self::Uart) => "Uarte",
+            (_, Self::Uart) => "Uart",
+            (Vendor::Nrf, Self::I2c) => "Twim",
+            (_, Self::I2c) => "I2c",
+            (Vendor::Nrf, Self::Spi) => "Spim",
+            (_, Self::Spi) => "Spi",
+        }
+    }
+
+    /// register block kinds of stm32-data, and instance name prefixes for descriptions without
+    /// them
+    fn matches(self, name: &str, registers: Option<&str>) -> bool {
+        let kinds: &[&str] = match self {
+            Self::Uart => &["usart", "lpuart", "uart", "uarte"],
+            Self::I2c => &["i2c", "twim"],
+            Self::Spi => &["spi", "spim"],
+        };
+        let prefixes: &[&str] = match self {
+            Self::Uart => &["USART", "LPUART", "UART"],
+            Self::I2c => &["I2C", "TWI"],
+            Self::Spi => &["SPI", "TWISPI"],
+        };
+        match registers {
+            Some(kind) => kinds.contains(&kind.to_lowercase().as_str()),
+            None => prefixes.iter().any(|p| name.starts_with(p)),
+        }
+    }
+}
+
+impl FromStr for Peripheral {
+    type Err = String;
+
+    /// `kind:INSTANCE`, optionally followed by `:signal=PIN,signal-dma=CHANNEL,...`
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut parts = spec.splitn(3, ':');
+        let kind = parts.next().unwrap_or_default();
+        let kind = PeripheralKind::from_str(kind, true).map_err(|_| {
+            let kinds = PeripheralKind::value_variants()
+                .iter()
+                .map(|k| name_of(*k))
+                .collect::<Vec<_>>();
+            format!(
+                "unknown peripheral \"{kind}\", expected one of: {}",
+                kinds.join(", ")
+            )
+        })?;
+        let instance = parts
+            .next()
+            .filter(|i| !i.is_empty())
+            .ok_or(format!(
+                "expected {}:<INSTANCE>, eg: uart:USART2",
+                name_of(kind)
+            ))?
+            .to_uppercase();
+
+        let mut peripheral = Self {
+            kind,
+            instance,
+            pins: BTreeMap::new(),
+            dma: BTreeMap::new(),
+            interrupts: BTreeMap::new(),
+        };
+        for choice in parts.next().unwrap_or_default().split(',') {
+            if choice.is_empty() {
+                continue;
+            }
+            let Some((signal, value)) = choice.split_once('=') else {
+                return Err(format!("expected signal=PIN, got \"{choice}\""));
+            };
+            let signal = signal.to_lowercase();
+            let value = value.to_uppercase();
+            match signal.strip_suffix("-dma") {
+                Some(signal) => peripheral.dma.insert(signal.to_owned(), value),
+                None => peripheral.pins.insert(signal, value),
+            };
+        }
+
+        Ok(peripheral)
+    }
+}
+
+impl Peripheral {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        spec.parse()
+    }
+
+    /// module the driver lives in, eg: "usart2"
+    pub fn module(&self) -> String {
+        self.instance.to_lowercase()
+    }
+
+    /// pins and DMA channels taken by the peripheral
+    pub fn resources(&self) -> impl Iterator<Item = &String> {
+        self.pins.values().chain(self.dma.values())
+    }
+
+    /// arguments of `init`, in order, as `(parameter, peripheral)`
+    fn params(&self, vendor: Vendor) -> Vec<(String, String)> {
+        let mut params = vec![(self.kind.module(vendor).to_owned(), self.instance.clone())];
+        for signal in self.kind.signals() {
+            params.push((signal.to_string(), self.pins[*signal].clone()));
+        }
+        for signal in self.kind.dma_signals(vendor) {
+            params.push((format!("{signal}_dma"), self.dma[*signal].clone()));
+        }
+        params
+    }
+
+    /// statement creating the driver in `main`, eg: "let usart2 = usart2::init(p.USART2, ...);"
+    pub fn init(&self, vendor: Vendor) -> String {
+        let args = self
+            .params(vendor)
+            .into_iter()
+            .map(|(_, peripheral)| format!("p.{peripheral}"))
+            .collect::<Vec<_>>();
+        format!("let {0} = {0}::init({1});", self.module(), args.join(", "))
+    }
+
+    /// statement spawning the example task of the module
+    pub fn spawn(&self, logging: Logging) -> String {
+        templates::spawn("spawner", &format!("{0}::run({0})", self.module()), logging)
+    }
+
+    /// content of the driver module: interrupt bindings, `init` and an example task
+    pub fn render(&self, vendor: Vendor, hal: &str, logging: Logging) -> String {
+        let module = self.kind.module(vendor);
+        let driver = self.kind.driver(vendor);
+        let instance = &self.instance;
+        let params = self.params(vendor);
+
+        let mut types = params.iter().map(|(_, p)| p.clone()).collect::<Vec<_>>();
+        types.sort();
+        types.dedup();
+
+        let mut uses = vec![];
+        match logging {
+            Logging::Defmt => uses.push("use defmt::*;".to_owned()),
+            Logging::Log => uses.push("use log::*;".to_owned()),
+            Logging::None => {}
+        }
+        let bindings = self.bindings(vendor, module);
+        if !bindings.is_empty() {
+            uses.push(format!("use {hal}::bind_interrupts;"));
+        }
+        if vendor == Vendor::St {
+            uses.push(format!("use {hal}::mode::Async;"));
+        }
+        uses.push(format!("use {hal}::peripherals::{{{}}};", types.join(", ")));
+        uses.push(format!("use {hal}::Peri;"));
+        if vendor == Vendor::St && self.kind == PeripheralKind::I2c {
+            uses.push(format!("use {hal}::time::Hertz;"));
+        }
+        uses.push(format!("use {hal}::{module}::{{self, {driver}}};"));
+        if self.kind != PeripheralKind::Uart {
+            uses.push("use embassy_time::{Duration, Timer};".into());
+        }
+        // as rustfmt orders them, modules before types
+        uses.sort_by_key(|u| {
+            u.split("::")
+                .map(|s| (s.starts_with(char::is_uppercase), s.to_owned()))
+                .collect::<Vec<_>>()
+        });
+
+        let ty = match vendor {
+            Vendor::St => format!("{driver}<'static, Async>"),
+            Vendor::Nrf => format!("{driver}<'static, {instance}>"),
+            Vendor::Rp => format!("{driver}<'static, {instance}, {module}::Async>"),
+        };
+        let config = format!("{module}::Config::default()");
+        let constructor = match (vendor, self.kind) {
+            (Vendor::St, PeripheralKind::Uart) => {
+                format!("{driver}::new({module}, rx, tx, Irqs, tx_dma, rx_dma, {config})")
+            }
+            (Vendor::St, PeripheralKind::I2c) => format!(
+                "{driver}::new({module}, scl, sda, Irqs, tx_dma, rx_dma, Hertz(100_000), {config})"
+            ),
+            (Vendor::St | Vendor::Rp, PeripheralKind::Spi) => {
+                format!("{driver}::new({module}, sck, mosi, miso, tx_dma, rx_dma, {config})")
+            }
+            (Vendor::Nrf, PeripheralKind::Uart) => {
+                format!("{driver}::new({module}, Irqs, rx, tx, {config})")
+            }
+            (Vendor::Nrf, PeripheralKind::I2c) => {
+                format!("{driver}::new({module}, Irqs, sda, scl, {config})")
+            }
+            (Vendor::Nrf, PeripheralKind::Spi) => {
+                format!("{driver}::new({module}, Irqs, sck, miso, mosi, {config})")
+            }
+            (Vendor::Rp, PeripheralKind::Uart) => {
+                format!("{driver}::new({module}, tx, rx, Irqs, tx_dma, rx_dma, {config})")
+            }
+            (Vendor::Rp, PeripheralKind::I2c) => {
+                format!("{driver}::new_async({module}, scl, sda, Irqs, {config})")
+            }
+        };
+        // only the STM32 UART checks its config when it is created
+        let constructor = match (vendor, self.kind, logging) {
+            (Vendor::St, PeripheralKind::Uart, Logging::Defmt) => format!("unwrap!({constructor})"),
+            (Vendor::St, PeripheralKind::Uart, _) => format!("{constructor}.unwrap()"),
+            _ => constructor,
+        };
+
+        let pins = self
+            .kind
+            .signals()
+            .iter()
+            .map(|signal| format!("{} ({})", self.pins[*signal], signal.to_uppercase()))
+            .collect::<Vec<_>>();
+        let (summary, task) = self.example(vendor, &ty, logging);
+        let params = params
+            .iter()
+            .map(|(name, peripheral)| format!("{name}: Peri<'static, {peripheral}>"))
+            .collect::<Vec<_>>();
+        // one parameter per line when the signature does not fit, as rustfmt does
+        let signature = format!("pub fn init({}) -> {ty} {{", params.join(", "));
+        let signature = match signature.len() <= 100 {
+            true => signature,
+            false => format!(
+                "pub fn init(\n    {},\n) -> {ty} {{",
+                params.join(",\n    ")
+            ),
+        };
+
+        format!(
+            r#"//! {instance} on {pins}, {summary}
+
+{uses}
+{bindings}
+{signature}
+    {constructor}
+}}
+
+{task}
+"#,
+            pins = pins.join(", "),
+            uses = uses.join("\n"),
+        )
+    }
+
+    /// the `bind_interrupts!` block, preceded by an empty line, or nothing
+    fn bindings(&self, vendor: Vendor, module: &str) -> String {
+        let instance = &self.instance;
+        // interrupts shared by several signals take all of their handlers
+        let mut handlers: Vec<(String, Vec<String>)> = vec![];
+        for signal in self.kind.interrupt_signals(vendor) {
+            let handler = match *signal {
+                "ev" => format!("{module}::EventInterruptHandler<{instance}>"),
+                "er" => format!("{module}::ErrorInterruptHandler<{instance}>"),
+                _ => format!("{module}::InterruptHandler<{instance}>"),
+            };
+            let irq = self.interrupts[*signal].clone();
+            match handlers.iter_mut().find(|(name, _)| *name == irq) {
+                Some((_, list)) => list.push(handler),
+                None => handlers.push((irq, vec![handler])),
+            }
+        }
+        if handlers.is_empty() {
+            return String::new();
+        }
+
+        let lines = handlers
+            .iter()
+            .map(|(irq, list)| format!("    {irq} => {};\n", list.join(", ")))
+            .collect::<String>();
+        format!("\nbind_interrupts!(struct Irqs {{\n{lines}}});\n")
+    }
+
+    /// what the example task does, and the task
+    fn example(&self, vendor: Vendor, ty: &str, logging: Logging) -> (&'static str, String) {
+        let instance = &self.instance;
+        let log = logging != Logging::None;
+        match self.kind {
+            PeripheralKind::Uart => {
+                let failed = match log {
+                    true => format!(
+                        r#"if let Err(e) = echoed {{
+            warn!("{instance} failed: {{:?}}", e);
+        }}"#
+                    ),
+                    false => "let _ = echoed;".into(),
+                };
+                (
+                    "echoes back what it receives",
+                    format!(
+                        r#"#[embassy_executor::task]
+pub async fn run(mut uart: {ty}) {{
+    let mut buf = [0u8; 1];
+    loop {{
+        let echoed = match uart.read(&mut buf).await {{
+            Ok(()) => uart.write(&buf).await,
+            Err(e) => Err(e),
+        }};
+        {failed}
+    }}
+}}"#
+                    ),
+                )
+            }
+            PeripheralKind::I2c => {
+                let read = match vendor {
+                    Vendor::Rp => "i2c.write_read_async(ADDRESS, [REGISTER], &mut value)",
+                    _ => "i2c.write_read(ADDRESS, &[REGISTER], &mut value)",
+                };
+                let (ok, failed) = match log {
+                    true => (
+                        "info!(\"register {:#x}: {:#x}\", REGISTER, value[0])".to_owned(),
+                        format!("Err(e) => warn!(\"{instance} failed: {{:?}}\", e)"),
+                    ),
+                    false => ("{}".into(), "Err(_) => {}".into()),
+                };
+                (
+                    "reads a register of a device every second",
+                    format!(
+                        r#"/// 7-bit address of the device on the bus
+const ADDRESS: u8 = 0x42;
+const REGISTER: u8 = 0x00;
+
+#[embassy_executor::task]
+pub async fn run(mut i2c: {ty}) {{
+    let mut value = [0u8; 1];
+    loop {{
+        match {read}.await {{
+            Ok(()) => {ok},
+            {failed},
+        }}
+        Timer::after(Duration::from_secs(1)).await;
+    }}
+}}"#
+                    ),
+                )
+            }
+            PeripheralKind::Spi => {
+                let (ok, failed) = match log {
+                    true => (
+                        "info!(\"read {:x}\", read)".to_owned(),
+                        format!("Err(e) => warn!(\"{instance} failed: {{:?}}\", e)"),
+                    ),
+                    false => ("{}".into(), "Err(_) => {}".into()),
+                };
+                (
+                    "sends a JEDEC ID command every second",
+                    format!(
+                        r#"/// drive the chip select of the device with an `Output` around each transfer
+#[embassy_executor::task]
+pub async fn run(mut spi: {ty}) {{
+    loop {{
+        let write = [0x9f, 0, 0, 0];
+        let mut read = [0u8; 4];
+        match spi.transfer(&mut read, &write).await {{
+            Ok(()) => {ok},
+            {failed},
+        }}
+        Timer::after(Duration::from_secs(1)).await;
+    }}
+}}"#
+                    ),
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for Peripheral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", name_of(self.kind), self.instance)
+    }
+}
+
+/// the parts of a stm32-data chip description peripherals are checked against, nRF and RP chips
+/// are described the same way
+#[derive(Debug, Deserialize)]
+pub struct ChipData {
+    name: String,
+    cores: Vec<Core>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Core {
+    peripherals: Vec<ChipPeripheral>,
+    #[serde(default)]
+    dma_channels: Vec<DmaChannel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChipPeripheral {
+    name: String,
+    registers: Option<Registers>,
+    /// empty when any pin can be routed to the peripheral, as on nRF
+    #[serde(default)]
+    pins: Vec<PinSignal>,
+    #[serde(default)]
+    interrupts: Vec<InterruptSignal>,
+    #[serde(default)]
+    dma_channels: Vec<DmaSignal>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Registers {
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PinSignal {
+    pin: String,
+    signal: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct InterruptSignal {
+    signal: String,
+    interrupt: String,
+}
+
+/// a fixed channel, or any channel behind a DMAMUX
+#[derive(Debug, Deserialize)]
+struct DmaSignal {
+    signal: String,
+    channel: Option<String>,
+    dmamux: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DmaChannel {
+    name: String,
+    dmamux: Option<String>,
+}
+
+impl ChipData {
+    /// `path` is a chip description, or a directory of them named like stm32-data's, eg:
+    /// STM32F446RE.json
+    pub fn load(path: &Path, mcu: &str) -> anyhow::Result<Self> {
+        let file = match path.is_dir() {
+            true => path.join(format!("{}.json", mcu.to_uppercase())),
+            false => path.to_owned(),
+        };
+        let content = std::fs::read_to_string(&file).map_err(|e| {
+            anyhow!(ValidationError(format!(
+                "Could not read the chip data of {mcu} at {}: {e}",
+                file.display()
+            )))
+        })?;
+        let chip: Self = serde_json::from_str(&content).map_err(|e| {
+            anyhow!(ValidationError(format!(
+                "Invalid chip data {}: {e}",
+                file.display()
+            )))
+        })?;
+        if !chip.name.eq_ignore_ascii_case(mcu) {
+            anyhow::bail!(ValidationError(format!(
+                "{} describes {}, not {mcu}",
+                file.display(),
+                chip.name
+            )));
+        }
+
+        Ok(chip)
+    }
+
+    fn peripherals(&self) -> impl Iterator<Item = &ChipPeripheral> {
+        self.cores.iter().flat_map(|core| &core.peripherals)
+    }
+
+    /// fills in the pins, DMA channels and interrupts `wanted` leaves out and checks the ones it
+    /// gives, `used` holds what other parts of the project already took and gets what this
+    /// peripheral takes, `defaults` are preferred pins, eg: the console of the board
+    pub fn resolve(
+        &self,
+        vendor: Vendor,
+        wanted: &Peripheral,
+        defaults: &BTreeMap<String, String>,
+        used: &mut Vec<String>,
+    ) -> Result<Peripheral, String> {
+        let kind = wanted.kind;
+        let instance = &wanted.instance;
+        let Some(peripheral) = self.peripherals().find(|p| p.name == *instance) else {
+            let instances = self
+                .peripherals()
+                .filter(|p| kind.matches(&p.name, p.registers.as_ref().map(|r| r.kind.as_str())))
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>();
+            return Err(format!(
+                "{} has no {instance}, expected one of: {}",
+                self.name,
+                instances.join(", ")
+            ));
+        };
+        if !kind.matches(
+            instance,
+            peripheral.registers.as_ref().map(|r| r.kind.as_str()),
+        ) {
+            return Err(format!("{instance} is not a {} peripheral", name_of(kind)));
+        }
+        let mut resolved = Peripheral {
+            kind,
+            instance: instance.clone(),
+            pins: BTreeMap::new(),
+            dma: BTreeMap::new(),
+            interrupts: BTreeMap::new(),
+        };
+
+        for signal in wanted.pins.keys() {
+            if !kind.signals().contains(&signal.as_str()) {
+                return Err(format!(
+                    "unknown signal \"{signal}\" for {instance}, expected one of: {}",
+                    kind.signals().join(", ")
+                ));
+            }
+        }
+        let dma_signals = kind.dma_signals(vendor);
+        if dma_signals.is_empty() && !wanted.dma.is_empty() {
+            return Err(format!(
+                "{instance} does not take DMA channels, leave them out"
+            ));
+        }
+        for signal in wanted.dma.keys() {
+            if !dma_signals.contains(&signal.as_str()) {
+                return Err(format!(
+                    "unknown DMA signal \"{signal}\" for {instance}, expected one of: {}",
+                    dma_signals.join(", ")
+                ));
+            }
+        }
+
+        for signal in kind.signals() {
+            let options = peripheral
+                .pins
+                .iter()
+                .filter(|p| p.signal.eq_ignore_ascii_case(signal))
+                .map(|p| p.pin.as_str())
+                .collect::<Vec<_>>();
+            // nRF peripherals list no pins, any of them can be routed
+            let any = peripheral.pins.is_empty();
+            let pin = match wanted.pins.get(*signal) {
+                Some(pin) if any || options.contains(&pin.as_str()) => pin.clone(),
+                Some(pin) => {
+                    return Err(format!(
+                        "{pin} cannot be the {} of {instance}, expected one of: {}",
+                        signal.to_uppercase(),
+                        options.join(", ")
+                    ))
+                }
+                None => match defaults
+                    .get(*signal)
+                    .filter(|pin| any || options.contains(&pin.as_str()))
+                {
+                    Some(pin) => pin.clone(),
+                    None if any => {
+                        return Err(format!(
+                            "any pin can be the {} of {instance}, pick one with {}:{instance}:{signal}=<PIN>",
+                            signal.to_uppercase(),
+                            name_of(kind)
+                        ))
+                    }
+                    None => match options.iter().find(|pin| !used.iter().any(|u| u == *pin)) {
+                        Some(pin) => pin.to_string(),
+                        None => {
+                            return Err(format!(
+                                "every pin that can be the {} of {instance} is taken: {}",
+                                signal.to_uppercase(),
+                                options.join(", ")
+                            ))
+                        }
+                    },
+                },
+            };
+            if used.contains(&pin) {
+                return Err(format!("{pin} is already used by the project"));
+            }
+            used.push(pin.clone());
+            resolved.pins.insert(signal.to_string(), pin);
+        }
+
+        let all_channels = self
+            .cores
+            .iter()
+            .flat_map(|core| &core.dma_channels)
+            .collect::<Vec<_>>();
+        for signal in dma_signals {
+            let mut options = vec![];
+            for dma in peripheral
+                .dma_channels
+                .iter()
+                .filter(|d| d.signal.eq_ignore_ascii_case(signal))
+            {
+                match (&dma.channel, &dma.dmamux) {
+                    (Some(channel), _) => options.push(channel.as_str()),
+                    (None, Some(dmamux)) => options.extend(
+                        all_channels
+                            .iter()
+                            .filter(|c| c.dmamux.as_ref() == Some(dmamux))
+                            .map(|c| c.name.as_str()),
+                    ),
+                    (None, None) => {}
+                }
+            }
+            // RP channels serve any peripheral
+            if peripheral.dma_channels.is_empty() && vendor == Vendor::Rp {
+                options.extend(all_channels.iter().map(|c| c.name.as_str()));
+            }
+            options.dedup();
+            if options.is_empty() {
+                return Err(format!(
+                    "{instance} has no DMA channel for {}, pick another instance",
+                    signal.to_uppercase()
+                ));
+            }
+
+            let channel = match wanted.dma.get(*signal) {
+                Some(channel) if options.contains(&channel.as_str()) => channel.clone(),
+                Some(channel) => {
+                    return Err(format!(
+                        "{channel} cannot serve the {} of {instance}, expected one of: {}",
+                        signal.to_uppercase(),
+                        options.join(", ")
+                    ))
+                }
+                None => match options.iter().find(|c| !used.iter().any(|u| u == *c)) {
+                    Some(channel) => channel.to_string(),
+                    None => {
+                        return Err(format!(
+                            "every DMA channel for the {} of {instance} is taken: {}",
+                            signal.to_uppercase(),
+                            options.join(", ")
+                        ))
+                    }
+                },
+            };
+            if used.contains(&channel) {
+                return Err(format!("{channel} is already used by the project"));
+            }
+            used.push(channel.clone());
+            resolved.dma.insert(signal.to_string(), channel);
+        }
+        for signal in kind.interrupt_signals(vendor) {
+            let Some(irq) = peripheral
+                .interrupts
+                .iter()
+                .find(|i| i.signal.eq_ignore_ascii_case(signal))
+            else {
+                return Err(format!(
+                    "the chip data has no {} interrupt for {instance}",
+                    signal.to_uppercase()
+                ));
+            };
+            resolved
+                .interrupts
+                .insert(signal.to_string(), irq.interrupt.clone());
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// resolves `wanted` in order against the chip data at `path`, around the pins of the board and
+/// of the `existing` peripherals
+pub fn resolve_all(
+    path: &Path,
+    vendor: Vendor,
+    mcu: &str,
+    board: Option<&Board>,
+    existing: &[Peripheral],
+    wanted: &[Peripheral],
+    executor_irq: Option<&str>,
+) -> anyhow::Result<Vec<Peripheral>> {
+    let chip = ChipData::load(path, mcu)?;
+    let mut used = existing
+        .iter()
+        .flat_map(|p| p.resources())
+        .cloned()
+        .collect::<Vec<_>>();
+    if let Some(board) = board {
+        used.extend(board.leds().iter().map(|led| led.pin.clone()));
+        used.extend(board.buttons().iter().map(|button| button.pin.clone()));
+    }
+
+    let mut resolved: Vec<Peripheral> = vec![];
+    for peripheral in wanted {
+        if existing
+            .iter()
+            .chain(&resolved)
+            .any(|p| p.instance == peripheral.instance)
+        {
+            anyhow::bail!(ValidationError(format!(
+                "{} is already in the project",
+                peripheral.instance
+            )));
+        }
+        // the console of the board is wired to the probe
+        let defaults = board
+            .and_then(|b| b.uart())
+            .filter(|uart| uart.peripheral == peripheral.instance)
+            .map(|uart| {
+                BTreeMap::from([
+                    ("tx".to_owned(), uart.tx.clone()),
+                    ("rx".to_owned(), uart.rx.clone()),
+                ])
+            })
+            .unwrap_or_default();
+        let peripheral = chip
+            .resolve(vendor, peripheral, &defaults, &mut used)
+            .map_err(|e| ValidationError(format!("Invalid peripheral {peripheral}: {e}")))?;
+        // `bind_interrupts!` and the executor's `#[interrupt]` would define the same handler
+        if let Some(irq) =
+            executor_irq.filter(|irq| peripheral.interrupts.values().any(|i| i == irq))
+        {
+            anyhow::bail!(ValidationError(format!(
+                "Invalid peripheral {peripheral}: its driver binds {irq}, which runs the interrupt \
+                 executor, pick another --executor-irq"
+            )));
+        }
+        resolved.push(peripheral);
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// fixed DMA channels on USART2, a DMAMUX on SPI1 and one interrupt for both I2C1 signals
+    const CHIP: &str = r#"{
+        "name": "STM32G0B1RE",
+        "cores": [{
+            "peripherals": [
+                {"name": "USART2", "registers": {"kind": "usart"},
+                 "pins": [{"pin": "PA2", "signal": "TX"}, {"pin": "PA3", "signal": "RX"},
+                          {"pin": "PD5", "signal": "TX"}, {"pin": "PD6", "signal": "RX"}],
+                 "interrupts": [{"signal": "GLOBAL", "interrupt": "USART2"}],
+                 "dma_channels": [{"signal": "RX", "channel": "DMA1_CH5"},
+                                  {"signal": "TX", "channel": "DMA1_CH6"}]},
+                {"name": "SPI1", "registers": {"kind": "spi"},
+                 "pins": [{"pin": "PA5", "signal": "SCK"}, {"pin": "PA7", "signal": "MOSI"},
+                          {"pin": "PA6", "signal": "MISO"}],
+                 "dma_channels": [{"signal": "RX", "dmamux": "DMAMUX1"},
+                                  {"signal": "TX", "dmamux": "DMAMUX1"}]},
+                {"name": "I2C1", "registers": {"kind": "i2c"},
+                 "pins": [{"pin": "PB8", "signal": "SCL"}, {"pin": "PB9", "signal": "SDA"}],
+                 "interrupts": [{"signal": "EV", "interrupt": "I2C1"},
+                                {"signal": "ER", "interrupt": "I2C1"}],
+                 "dma_channels": [{"signal": "RX", "dmamux": "DMAMUX1"},
+                                  {"signal": "TX", "dmamux": "DMAMUX1"}]},
+                {"name": "TIM2", "registers": {"kind": "timer"}}
+            ],
+            "dma_channels": [{"name": "DMA1_CH1", "dmamux": "DMAMUX1"},
+                             {"name": "DMA1_CH2", "dmamux": "DMAMUX1"},
+                             {"name": "DMA1_CH3", "dmamux": "DMAMUX1"},
+                             {"name": "DMA1_CH5"}, {"name": "DMA1_CH6"}]
+        }]
+    }"#;
+
+    fn resolve(spec: &str, used: &mut Vec<String>) -> Result<Peripheral, String> {
+        let chip: ChipData = serde_json::from_str(CHIP).unwrap();
+        chip.resolve(Vendor::St, &spec.parse()?, &BTreeMap::new(), used)
+    }
+
+    #[test]
+    fn defaults_to_the_first_pins_and_channels() {
+        let mut used = vec![];
+        let uart = resolve("uart:USART2", &mut used).unwrap();
+        assert_eq!(uart.pins["tx"], "PA2");
+        assert_eq!(uart.pins["rx"], "PA3");
+        assert_eq!(uart.dma["tx"], "DMA1_CH6");
+        assert_eq!(uart.dma["rx"], "DMA1_CH5");
+        assert_eq!(uart.interrupts["global"], "USART2");
+        assert_eq!(used, ["PA2", "PA3", "DMA1_CH6", "DMA1_CH5"]);
+    }
+
+    #[test]
+    fn skips_pins_taken_by_the_project() {
+        let mut used = vec!["PA2".to_owned()];
+        let uart = resolve("uart:USART2", &mut used).unwrap();
+        assert_eq!(uart.pins["tx"], "PD5");
+        assert_eq!(uart.pins["rx"], "PA3");
+    }
+
+    #[test]
+    fn prefers_the_pins_of_the_board() {
+        let chip: ChipData = serde_json::from_str(CHIP).unwrap();
+        let defaults = BTreeMap::from([
+            ("tx".to_owned(), "PD5".to_owned()),
+            ("rx".to_owned(), "PD6".to_owned()),
+        ]);
+        let uart = chip
+            .resolve(
+                Vendor::St,
+                &"uart:USART2".parse().unwrap(),
+                &defaults,
+                &mut vec![],
+            )
+            .unwrap();
+        assert_eq!(
+            (uart.pins["tx"].as_str(), uart.pins["rx"].as_str()),
+            ("PD5", "PD6")
+        );
+    }
+
+    #[test]
+    fn expands_a_dmamux_to_its_channels() {
+        let mut used = vec![];
+        let spi = resolve("spi:SPI1", &mut used).unwrap();
+        assert_eq!(spi.dma["tx"], "DMA1_CH1");
+        assert_eq!(spi.dma["rx"], "DMA1_CH2");
+
+        let err = resolve("i2c:I2C1", &mut used).unwrap_err();
+        assert!(
+            err.contains("every DMA channel for the RX of I2C1 is taken"),
+            "{err}"
+        );
+        let err = resolve("i2c:I2C1:rx-dma=DMA1_CH5", &mut vec![]).unwrap_err();
+        assert!(
+            err.contains("DMA1_CH5 cannot serve the RX of I2C1"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn rejects_conflicts() {
+        let mut used = vec!["PB8".to_owned()];
+        let err = resolve("i2c:I2C1", &mut used).unwrap_err();
+        assert!(
+            err.contains("every pin that can be the SCL of I2C1 is taken"),
+            "{err}"
+        );
+        let err = resolve("i2c:I2C1:scl=PB8", &mut used).unwrap_err();
+        assert_eq!(err, "PB8 is already used by the project");
+        let err = resolve("uart:USART2:tx=PB8", &mut vec![]).unwrap_err();
+        assert!(err.starts_with("PB8 cannot be the TX of USART2"), "{err}");
+        let err = resolve("uart:TIM2", &mut vec![]).unwrap_err();
+        assert_eq!(err, "TIM2 is not a uart peripheral");
+        let err = resolve("spi:SPI2", &mut vec![]).unwrap_err();
+        assert_eq!(err, "STM32G0B1RE has no SPI2, expected one of: SPI1");
+    }
+
+    #[test]
+    fn binds_the_ev_and_er_handlers_to_a_shared_interrupt() {
+        let i2c = resolve("i2c:I2C1", &mut vec![]).unwrap();
+        assert_eq!(i2c.interrupts["ev"], "I2C1");
+        assert_eq!(i2c.interrupts["er"], "I2C1");
+        let module = i2c.render(Vendor::St, "embassy_stm32", Logging::Defmt);
+        assert!(module.contains(
+            "    I2C1 => i2c::EventInterruptHandler<I2C1>, i2c::ErrorInterruptHandler<I2C1>;\n"
+        ));
+        assert!(module.contains("    i2c: Peri<'static, I2C1>,\n"));
+    }
+}
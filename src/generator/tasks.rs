@@ -0,0 +1,505 @@
+use clap::ValueEnum;
+use proc_macro2::LineColumn;
+use serde::{Deserialize, Serialize};
+use syn::{
+    spanned::Spanned,
+    visit::{self, Visit},
+    Block, Expr, ExprMethodCall, FnArg, Item, ItemFn, Macro, Pat, Stmt,
+};
+
+use crate::commands::create::Logging;
+
+use super::templates;
+
+/// what a new task waits on
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Input {
+    /// A queue of messages, each one is handled
+    Channel,
+    /// The latest value, older ones are overwritten
+    Signal,
+}
+
+/// a module holding one `#[embassy_executor::task]`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Task {
+    pub name: String,
+    /// instances of the task that can run at once
+    pub pool_size: Option<u32>,
+    pub input: Option<Input>,
+    /// type of the messages on the input, eg: "u32"
+    pub message: String,
+    /// messages the channel holds
+    pub capacity: usize,
+}
+
+impl Task {
+    /// checks the names and sizes are usable in Rust code
+    pub fn validate(&self) -> Result<(), String> {
+        if syn::parse_str::<syn::Ident>(&self.name).is_err()
+            || self
+                .name
+                .starts_with(|c: char| c.is_ascii_digit() || c == '_')
+            || self.name.chars().any(|c| c.is_ascii_uppercase())
+        {
+            return Err(format!(
+                "\"{}\" is not a snake_case module name, eg: blinker",
+                self.name
+            ));
+        }
+        if self.name == "main" {
+            return Err("main is taken by main.rs".into());
+        }
+        if self.pool_size == Some(0) {
+            return Err("the pool size must be at least 1".into());
+        }
+        if syn::parse_str::<syn::Type>(&self.message).is_err() {
+            return Err(format!("\"{}\" is not a Rust type", self.message));
+        }
+        if self.capacity == 0 {
+            return Err("the channel capacity must be at least 1".into());
+        }
+
+        Ok(())
+    }
+
+    /// the task as spawned from main.rs
+    pub fn call(&self) -> String {
+        format!("{}::run()", self.name)
+    }
+
+    pub fn render(&self, logging: Logging) -> String {
+        let name = &self.name;
+        let message = &self.message;
+        let log = logging != Logging::None;
+
+        let mut uses = match logging {
+            Logging::Defmt => vec!["use defmt::*;"],
+            Logging::Log => vec!["use log::*;"],
+            Logging::None => vec![],
+        };
+        let (summary, statics, wait) = match self.input {
+            Some(Input::Channel) => {
+                uses.push("use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;");
+                uses.push("use embassy_sync::channel::Channel;");
+                (
+                    format!("fed through `{name}::CHANNEL`"),
+                    format!(
+                        r#"
+/// holds up to {capacity} messages, send them with `{name}::CHANNEL.send(..).await`
+pub static CHANNEL: Channel<CriticalSectionRawMutex, {message}, {capacity}> = Channel::new();
+"#,
+                        capacity = self.capacity
+                    ),
+                    "CHANNEL.receive().await",
+                )
+            }
+            Some(Input::Signal) => {
+                uses.push("use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;");
+                uses.push("use embassy_sync::signal::Signal;");
+                (
+                    format!("woken through `{name}::SIGNAL`"),
+                    format!(
+                        r#"
+/// keeps the latest value, set it with `{name}::SIGNAL.signal(..)`
+pub static SIGNAL: Signal<CriticalSectionRawMutex, {message}> = Signal::new();
+"#
+                    ),
+                    "SIGNAL.wait().await",
+                )
+            }
+            None => {
+                uses.push("use embassy_time::{Duration, Timer};");
+                ("runs every second".to_owned(), String::new(), "")
+            }
+        };
+        uses.sort();
+
+        let body = match (self.input, log) {
+            (Some(_), true) => format!(
+                "let message = {wait};\n        info!(\"{name} received {{:?}}\", message);"
+            ),
+            (Some(_), false) => format!("let _message = {wait};"),
+            (None, true) => {
+                format!("Timer::after(Duration::from_secs(1)).await;\n        info!(\"{name}\");")
+            }
+            (None, false) => "Timer::after(Duration::from_secs(1)).await;".into(),
+        };
+        let attribute = match self.pool_size {
+            Some(size) => format!(
+                "/// up to {size} instances can run at once, spawn more with \
+                 `spawner.spawn({name}::run())`\n#[embassy_executor::task(pool_size = {size})]"
+            ),
+            None => "#[embassy_executor::task]".into(),
+        };
+
+        format!(
+            r#"//! the {name} task, {summary}
+
+{uses}
+{statics}
+{attribute}
+pub async fn run() {{
+    loop {{
+        {body}
+    }}
+}}
+"#,
+            uses = uses.join("\n"),
+        )
+    }
+}
+
+/// adds `mod {module};` and a spawn of `task` to the source of main.rs, parsed with syn so
+/// formatting and comments are kept, `None` when there is no spawner to spawn from
+pub fn wire(
+    source: &str,
+    module: &str,
+    task: &str,
+    logging: Logging,
+) -> Result<Option<String>, String> {
+    let file =
+        syn::parse_file(source).map_err(|e| format!("line {}: {e}", e.span().start().line))?;
+
+    // `mod` declarations, not inline modules
+    let mods = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Mod(m) if m.content.is_none() => Some(m),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if mods.iter().any(|m| m.ident == module) {
+        return Err(format!("main.rs already declares `mod {module}`"));
+    }
+
+    let Some(site) = spawn_site(&file.items) else {
+        return Ok(None);
+    };
+
+    // (start, end, replacement) in bytes
+    let mut edits = vec![];
+    // kept sorted, as rustfmt does
+    let declaration = format!("mod {module};\n");
+    let at = match mods.iter().find(|m| m.ident.to_string().as_str() > module) {
+        Some(next) => line_start(source, next.span().start().line),
+        None => match mods.last() {
+            Some(last) => line_start(source, last.span().end().line + 1),
+            None => {
+                // after the imports, or at the top of the items
+                let line = file
+                    .items
+                    .iter()
+                    .rfind(|item| matches!(item, Item::Use(_)))
+                    .map(|u| u.span().end().line + 1)
+                    .or(file.items.first().map(|item| item.span().start().line))
+                    .unwrap_or(source.lines().count() + 1);
+                let at = line_start(source, line);
+                edits.push((at, at, "\n".to_owned()));
+                at
+            }
+        },
+    };
+    edits.push((at, at, declaration));
+
+    // `_spawner` is renamed now that it is used
+    let spawner = match site.spawner.to_string().trim_start_matches('_') {
+        "" => "spawner".to_owned(),
+        name => name.to_owned(),
+    };
+    if *site.spawner != spawner {
+        let start = offset(source, site.spawner.span().start());
+        let end = offset(source, site.spawner.span().end());
+        edits.push((start, end, spawner.clone()));
+    }
+    let (at, statement) = spawn_edit(source, site.block, &spawner, task, logging);
+    edits.push((at, at, statement));
+
+    // applied from the end so the offsets stay valid, edits at the same place keep their order
+    edits.sort_by_key(|(start, _, _)| *start);
+    let mut edited = source.to_owned();
+    for (start, end, text) in edits.into_iter().rev() {
+        edited.replace_range(start..end, &text);
+    }
+
+    Ok(Some(edited))
+}
+
+/// where new tasks are spawned: the body of `#[embassy_executor::main]`, or the closure given
+/// to `Executor::run`
+struct Site<'a> {
+    spawner: &'a syn::Ident,
+    block: &'a Block,
+}
+
+fn spawn_site(items: &[Item]) -> Option<Site<'_>> {
+    let main = items.iter().find_map(|item| match item {
+        Item::Fn(f) if is_embassy_main(f) => Some(f),
+        _ => None,
+    });
+    if let Some(main) = main {
+        let spawner = main.sig.inputs.iter().find_map(|input| match input {
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(ident) => Some(&ident.ident),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })?;
+        return Some(Site {
+            spawner,
+            block: &main.block,
+        });
+    }
+
+    let mut finder = RunClosure { site: None };
+    for item in items {
+        finder.visit_item(item);
+    }
+    finder.site
+}
+
+fn is_embassy_main(f: &ItemFn) -> bool {
+    f.attrs.iter().any(|attr| {
+        let segments = attr
+            .path()
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect::<Vec<_>>();
+        segments == ["embassy_executor", "main"]
+    })
+}
+
+/// finds `executor.run(|spawner| { .. })`
+struct RunClosure<'a> {
+    site: Option<Site<'a>>,
+}
+
+impl<'a> Visit<'a> for RunClosure<'a> {
+    fn visit_expr_method_call(&mut self, call: &'a ExprMethodCall) {
+        if self.site.is_none() && call.method == "run" {
+            if let Some(Expr::Closure(closure)) = call.args.first() {
+                let spawner = match closure.inputs.first() {
+                    Some(Pat::Ident(ident)) => Some(&ident.ident),
+                    Some(Pat::Type(typed)) => match &*typed.pat {
+                        Pat::Ident(ident) => Some(&ident.ident),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if let (Some(spawner), Expr::Block(body)) = (spawner, &*closure.body) {
+                    self.site = Some(Site {
+                        spawner,
+                        block: &body.block,
+                    });
+                    return;
+                }
+            }
+        }
+        visit::visit_expr_method_call(self, call);
+    }
+}
+
+/// looks for `.spawn(..)` calls, including the ones wrapped in macros such as `unwrap!`
+struct SpawnCall {
+    found: bool,
+}
+
+impl<'a> Visit<'a> for SpawnCall {
+    fn visit_expr_method_call(&mut self, call: &'a ExprMethodCall) {
+        self.found |= call.method == "spawn";
+        visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_macro(&mut self, mac: &'a Macro) {
+        if let Ok(expr) = mac.parse_body::<Expr>() {
+            self.visit_expr(&expr);
+        }
+    }
+}
+
+fn spawns(stmt: &Stmt) -> bool {
+    let mut finder = SpawnCall { found: false };
+    finder.visit_stmt(stmt);
+    finder.found
+}
+
+/// inserts the spawn after the last one, or before the main loop, or at the end of the block
+fn spawn_edit(
+    source: &str,
+    block: &Block,
+    spawner: &str,
+    task: &str,
+    logging: Logging,
+) -> (usize, String) {
+    let statement = templates::spawn(spawner, task, logging);
+    let indent = |stmt: &Stmt| " ".repeat(stmt.span().start().column);
+
+    if let Some(last) = block.stmts.iter().rev().find(|stmt| spawns(stmt)) {
+        let line = last.span().end().line + 1;
+        return (
+            line_start(source, line),
+            format!("{}{statement}\n", indent(last)),
+        );
+    }
+    let main_loop = block
+        .stmts
+        .iter()
+        .find(|stmt| matches!(stmt, Stmt::Expr(Expr::Loop(_) | Expr::While(_), _)));
+    if let Some(main_loop) = main_loop {
+        let line = main_loop.span().start().line;
+        return (
+            line_start(source, line),
+            format!("{}{statement}\n\n", indent(main_loop)),
+        );
+    }
+    match block.stmts.last() {
+        Some(last) => {
+            let line = last.span().end().line + 1;
+            (
+                line_start(source, line),
+                format!("{}{statement}\n", indent(last)),
+            )
+        }
+        None => {
+            let brace = block.brace_token.span.open().start();
+            (
+                line_start(source, brace.line + 1),
+                format!("{}{statement}\n", " ".repeat(brace.column + 4)),
+            )
+        }
+    }
+}
+
+/// byte offset of the start of a 1-based line, the end of the source past the last line
+fn line_start(source: &str, line: usize) -> usize {
+    source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum()
+}
+
+/// byte offset of a span location, whose column counts chars
+fn offset(source: &str, at: LineColumn) -> usize {
+    let start = line_start(source, at.line);
+    start
+        + source[start..]
+            .chars()
+            .take(at.column)
+            .map(char::len_utf8)
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wire_blinker(source: &str) -> Option<String> {
+        wire(source, "blinker", "blinker::run()", Logging::Defmt).unwrap()
+    }
+
+    #[test]
+    fn renames_an_unused_spawner() {
+        let source = r#"use embassy_executor::Spawner;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    loop {
+        Timer::after(Duration::from_millis(500)).await;
+    }
+}
+"#;
+        let expected = r#"use embassy_executor::Spawner;
+
+mod blinker;
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_nrf::init(Default::default());
+
+    unwrap!(spawner.spawn(blinker::run()));
+
+    loop {
+        Timer::after(Duration::from_millis(500)).await;
+    }
+}
+"#;
+        assert_eq!(wire_blinker(source).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn keeps_mods_sorted_and_spawns_after_the_last_spawn() {
+        let source = r#"use embassy_executor::Spawner;
+
+mod adc;
+mod uart;
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    unwrap!(spawner.spawn(adc::run()));
+    unwrap!(spawner.spawn(uart::run()));
+}
+"#;
+        let expected = r#"use embassy_executor::Spawner;
+
+mod adc;
+mod blinker;
+mod uart;
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    unwrap!(spawner.spawn(adc::run()));
+    unwrap!(spawner.spawn(uart::run()));
+    unwrap!(spawner.spawn(blinker::run()));
+}
+"#;
+        assert_eq!(wire_blinker(source).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn spawns_from_the_executor_run_closure() {
+        let source = r#"use embassy_executor::Executor;
+
+#[entry]
+fn main() -> ! {
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        unwrap!(spawner.spawn(run_low()));
+    })
+}
+"#;
+        let expected = r#"use embassy_executor::Executor;
+
+mod blinker;
+
+#[entry]
+fn main() -> ! {
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        unwrap!(spawner.spawn(run_low()));
+        unwrap!(spawner.spawn(blinker::run()));
+    })
+}
+"#;
+        assert_eq!(wire_blinker(source).as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn leaves_sources_without_a_spawner() {
+        let source = "#[entry]\nfn main() -> ! {\n    loop {}\n}\n";
+        assert_eq!(wire_blinker(source), None);
+    }
+
+    #[test]
+    fn rejects_a_declared_module() {
+        let source =
+            "mod blinker;\n\n#[embassy_executor::main]\nasync fn main(spawner: Spawner) {}\n";
+        assert!(wire(source, "blinker", "blinker::run()", Logging::Defmt).is_err());
+    }
+}
@@ -3,9 +3,22 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::{commands::create::Vendor, git::Git};
+use anyhow::anyhow;
+use clap::ValueEnum;
 
-use super::generate::GeneratorConfig;
+use crate::{
+    commands::create::{Editor, Executor, Logging, Panic, Runner, Vendor},
+    error::ValidationError,
+};
+
+use super::{
+    boards::Board,
+    clocks::Sysclk,
+    data::{tick_feature, DATA},
+    generate::GeneratorConfig,
+    lock::Lock,
+    tasks,
+};
 
 pub struct TemplateBuilder {
     root: Template,
@@ -34,8 +47,9 @@ impl TemplateBuilder {
         })
     }
 
-    pub fn build(&self) -> anyhow::Result<PathBuf> {
-        for item in self.root.flatten(Path::new(".")) {
+    /// writes the project into `parent`, returns the project directory
+    pub fn build(&self, parent: &Path) -> anyhow::Result<PathBuf> {
+        for item in self.root.flatten(parent) {
             match item {
                 TemplateItem::Dir { path } => std::fs::DirBuilder::new().create(path)?,
                 TemplateItem::File { content, path } => {
@@ -45,10 +59,96 @@ impl TemplateBuilder {
             }
         }
 
-        Ok(self.cfg.name.clone().into())
+        let path = parent.join(&self.cfg.name);
+        if let Some(template) = &self.cfg.template {
+            copy_dir(template, &path)?;
+        }
+
+        Ok(path)
+    }
+
+    /// records the inputs and the hashes of the files in `path` into the lock file, this has to
+    /// run once the files are formatted
+    pub fn write_lock(&self, path: &Path) -> anyhow::Result<()> {
+        let files = self
+            .files()
+            .into_iter()
+            .filter_map(|file| Some(file.strip_prefix(&self.cfg.name).ok()?.to_owned()))
+            .collect::<Vec<_>>();
+
+        Lock::new(&self.cfg, path, &files)?.write(path)
+    }
+
+    pub fn cfg(&self) -> &GeneratorConfig {
+        &self.cfg
+    }
+
+    /// paths of the files that will be written, relative to the parent directory
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self.generated_files();
+        for file in self.template_files() {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+        files
+    }
+
+    /// generated files the template directory replaces
+    pub fn overridden_files(&self) -> Vec<PathBuf> {
+        let generated = self.generated_files();
+        self.template_files()
+            .into_iter()
+            .filter(|file| generated.contains(file))
+            .collect()
+    }
+
+    fn generated_files(&self) -> Vec<PathBuf> {
+        self.root
+            .flatten(Path::new(""))
+            .into_iter()
+            .filter_map(|item| match item {
+                TemplateItem::File { path, .. } => Some(path),
+                TemplateItem::Dir { .. } => None,
+            })
+            .collect()
+    }
+
+    fn template_files(&self) -> Vec<PathBuf> {
+        fn walk(dir: &Path, path: &Path, files: &mut Vec<PathBuf>) {
+            for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+                let path = path.join(entry.file_name());
+                match entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    true => walk(&entry.path(), &path, files),
+                    false => files.push(path),
+                }
+            }
+        }
+
+        let mut files = vec![];
+        if let Some(template) = &self.cfg.template {
+            walk(template, Path::new(&self.cfg.name), &mut files);
+        }
+        files
     }
 }
 
+/// copies the files of a template directory over the generated project
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let path = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &path)?;
+        } else {
+            std::fs::copy(entry.path(), path)?;
+        }
+    }
+
+    Ok(())
+}
+
 enum Template {
     File {
         name: String,
@@ -62,6 +162,15 @@ enum Template {
 
 impl Template {
     async fn root(cfg: &GeneratorConfig) -> anyhow::Result<Self> {
+        let mut app = Vec::from([
+            Template::app_src(cfg).await?,
+            Template::build_rs(cfg).await?,
+            Template::app_cargo_toml(cfg).await?,
+        ]);
+        if cfg.panic == Panic::Persist {
+            app.push(Template::panic_persist_x(cfg).await?);
+        }
+
         let children = match cfg.workspace {
             true => Vec::from([
                 Template::dot_cargo(cfg).await?,
@@ -71,11 +180,7 @@ impl Template {
                     children: Vec::from([
                         Template::Dir {
                             name: "app".into(),
-                            children: Vec::from([
-                                Template::app_src(cfg).await?,
-                                Template::build_rs(cfg).await?,
-                                Template::app_cargo_toml(cfg).await?,
-                            ]),
+                            children: app,
                         },
                         Template::Dir {
                             name: "my_lib".into(),
@@ -90,17 +195,23 @@ impl Template {
                 Template::workspace_cargo_toml(cfg).await?,
                 Template::rust_toolchain(cfg).await?,
             ]),
-            false => Vec::from([
-                Template::dot_cargo(cfg).await?,
-                Template::dot_vscode(cfg).await?,
-                Template::app_src(cfg).await?,
-                Template::dot_gitignore(cfg).await?,
-                Template::build_rs(cfg).await?,
-                Template::app_cargo_toml(cfg).await?,
-                Template::rust_toolchain(cfg).await?,
-            ]),
+            false => {
+                let mut children = Vec::from([
+                    Template::dot_cargo(cfg).await?,
+                    Template::dot_vscode(cfg).await?,
+                    Template::dot_gitignore(cfg).await?,
+                    Template::rust_toolchain(cfg).await?,
+                ]);
+                children.extend(app);
+                children
+            }
         };
 
+        let children = children
+            .into_iter()
+            .filter(|child| cfg.editor == Editor::Vscode || child.name() != ".vscode")
+            .collect();
+
         Ok(Template::Dir {
             name: cfg.name.clone(),
             children,
@@ -108,8 +219,15 @@ impl Template {
     }
 
     async fn dot_cargo(cfg: &GeneratorConfig) -> anyhow::Result<Self> {
-        let mcu = cfg.mcu.as_str();
+        let runner = runner(cfg.runner, &cfg.mcu);
         let target = cfg.target.as_str();
+        let env = match cfg.logging {
+            Logging::Defmt => {
+                let log_level = cfg.log_level.to_possible_value().unwrap(); // should be safe
+                format!("\n                \n[env]\nDEFMT_LOG = \"{}\"", log_level.get_name())
+            }
+            Logging::Log | Logging::None => "".into(),
+        };
 
         Ok(Template::Dir {
             name: ".cargo".into(),
@@ -117,62 +235,306 @@ impl Template {
                 name: "config.toml".into(),
                 content: format!(
                     r#"[target.'cfg(all(target_arch = "arm", target_os = "none"))']
-runner = "probe-run --chip {mcu} --speed 1000 --connect-under-reset"
+runner = "{runner}"
                 
 [build]
-target = "{target}"
-                
-[env]
-DEFMT_LOG = "trace""#
+target = "{target}"{env}"#
                 ),
             }],
         })
     }
 
-    async fn dot_vscode(_cfg: &GeneratorConfig) -> anyhow::Result<Self> {
+    async fn dot_vscode(cfg: &GeneratorConfig) -> anyhow::Result<Self> {
+        let target = cfg.target.as_str();
+
         Ok(Template::Dir {
             name: ".vscode".into(),
             children: vec![Template::File {
                 name: "settings.json".into(),
-                content: r#"{
-    "rust-analyzer.cargo.target": "thumbv7em-none-eabihf",
+                content: format!(
+                    r#"{{
+    "rust-analyzer.cargo.target": "{target}",
     "rust-analyzer.checkOnSave.allTargets": false
-}"#
-                    .into(),
+}}"#
+                ),
             }],
         })
     }
 
     async fn app_src(cfg: &GeneratorConfig) -> anyhow::Result<Self> {
         let embassy_crate = vendor_to_crate(cfg.vendor).replace('-', "_");
+        let features = match cfg.nightly() {
+            true => "\n#![feature(type_alias_impl_trait)]",
+            false => "",
+        };
+        let (imports, hello, tick) = match cfg.logging {
+            Logging::Defmt => (
+                "use defmt::*;\n",
+                "\n\n    info!(\"Hello World!\");",
+                "\n        info!(\"Hello!\");",
+            ),
+            Logging::Log => (
+                "use log::*;\n",
+                "\n\n    info!(\"Hello World!\");",
+                "\n        info!(\"Hello!\");",
+            ),
+            Logging::None => ("", "", ""),
+        };
+        let panic_crate = panic_crate(cfg.panic).replace('-', "_");
+        let crates = match cfg.logging {
+            Logging::Defmt => format!("use {{defmt_rtt as _, {panic_crate} as _}};"),
+            Logging::Log | Logging::None => format!("use {panic_crate} as _;"),
+        };
+        let persisted = match (cfg.panic, cfg.logging) {
+            (Panic::Persist, Logging::Defmt | Logging::Log) => {
+                "\n\n    if let Some(msg) = panic_persist::get_panic_message_utf8() {\n        error!(\"Previous panic: {}\", msg);\n    }"
+            }
+            _ => "",
+        };
+        let plan = match (cfg.sysclk, DATA.clocks(&cfg.mcu)) {
+            (Some(sysclk), Some(clocks)) => Some(
+                clocks
+                    .plan(cfg.hse, Sysclk::Hz(sysclk))
+                    .map_err(|e| ValidationError(format!("Invalid clock configuration: {e}")))?,
+            ),
+            _ => None,
+        };
+        let board = cfg.board.as_deref().and_then(|name| DATA.board(name));
+        let bypass = board
+            .and_then(|b| b.hse())
+            .is_some_and(|hse| hse.bypass && cfg.hse == Some(hse.freq));
+        let init = match plan {
+            Some(plan) => format!(
+                "{}\n    let p = {embassy_crate}::init(config);",
+                plan.render(bypass)
+            ),
+            None => format!("    let p = {embassy_crate}::init(Default::default());"),
+        };
+        // installed first, so the HAL can log while it starts
+        let init = match cfg.logging {
+            Logging::Log => format!("    logger::init();\n\n{init}"),
+            Logging::Defmt | Logging::None => init,
+        };
+        let spawn = |task: &str| spawn("spawner", task, cfg.logging);
+        let example = board.and_then(|board| board_example(cfg, board, &embassy_crate));
+        // each peripheral is created after the board's pins and runs its example task
+        let mut mods = cfg
+            .peripherals
+            .iter()
+            .map(|p| format!("\nmod {};", p.module()))
+            .collect::<Vec<_>>();
+        if cfg.logging == Logging::Log {
+            mods.push("\nmod logger;".into());
+        }
+        mods.sort();
+        let mods = mods.concat();
+        let mods = match mods.is_empty() {
+            true => mods,
+            false => format!("\n{mods}"),
+        };
+        let inits = cfg
+            .peripherals
+            .iter()
+            .map(|p| format!("\n    {}", p.init(cfg.vendor)))
+            .collect::<String>();
+        let inits = match inits.is_empty() {
+            true => inits,
+            false => format!("\n{inits}"),
+        };
+        let tasks = cfg
+            .peripherals
+            .iter()
+            .map(|p| p.spawn(cfg.logging))
+            .collect::<Vec<_>>();
+        let mut modules = cfg
+            .peripherals
+            .iter()
+            .map(|p| Template::File {
+                name: format!("{}.rs", p.module()),
+                content: p.render(cfg.vendor, &embassy_crate, cfg.logging),
+            })
+            .collect::<Vec<_>>();
+        if cfg.logging == Logging::Log {
+            modules.push(Template::File {
+                name: "logger.rs".into(),
+                content: LOGGER_RS.into(),
+            });
+        }
+        let (atomics, gpio, statics) = match &example {
+            Some(example) => (
+                example.atomics,
+                example.gpio.as_str(),
+                example.statics,
+            ),
+            None => ("", "", ""),
+        };
 
-        Ok(Template::Dir {
-            name: "src".into(),
-            children: vec![Template::File {
-                name: "main.rs".into(),
-                content: format!(
-                    r#"#![no_std]
-#![no_main]
-#![feature(type_alias_impl_trait)]
+        if let Some(irq) = &cfg.executor_irq {
+            let high_tick = tick.replace("Hello!", "Hello from the interrupt executor!");
+            let run_high = format!(
+                r#"/// runs in the {irq} interrupt and preempts the tasks of the thread executor
+#[embassy_executor::task]
+async fn run_high() {{
+    loop {{
+        Timer::after(Duration::from_millis(500)).await;{high_tick}
+    }}
+}}"#
+            );
+            // the board's button is watched from the interrupt executor, its LED blinks from
+            // the thread executor
+            let (high_task, spawn_high, low_args, low_loop, setup, spawn_low) = match &example {
+                Some(example) => (
+                    example.button_task.clone().unwrap_or(run_high),
+                    spawn(match example.button_task {
+                        Some(_) => "watch_button(button)",
+                        None => "run_high()",
+                    }),
+                    "mut led: Output<'static>",
+                    example.blink.clone(),
+                    // the LED moves into the blink task
+                    example.setup.replace("let mut led", "let led"),
+                    spawn("run_low(led)"),
+                ),
+                None => (
+                    run_high,
+                    spawn("run_high()"),
+                    "",
+                    format!("        Timer::after(Duration::from_millis(500)).await;{tick}"),
+                    String::new(),
+                    spawn("run_low()"),
+                ),
+            };
+            let spawn_low = tasks
+                .iter()
+                .fold(spawn_low, |all, task| format!("{all}\n        {task}"));
 
-use defmt::*;
-use embassy_executor::Spawner;
+            let main = format!(
+                r#"#![no_std]
+#![no_main]{features}
+
+{atomics}{imports}use cortex_m_rt::entry;
+use embassy_executor::{{Executor, InterruptExecutor}};
+{gpio}use {embassy_crate}::interrupt;
+use {embassy_crate}::interrupt::{{InterruptExt, Priority}};
 use embassy_time::{{Duration, Timer}};
-use {{defmt_rtt as _, panic_probe as _}};
+use static_cell::StaticCell;
+{crates}{mods}
 
-#[embassy_executor::main]
-async fn main(_spawner: Spawner) {{
-    let p = {embassy_crate}::init(Default::default());
+static EXECUTOR_HIGH: InterruptExecutor = InterruptExecutor::new();
+static EXECUTOR_LOW: StaticCell<Executor> = StaticCell::new();{statics}
+
+#[interrupt]
+unsafe fn {irq}() {{
+    EXECUTOR_HIGH.on_interrupt()
+}}
 
-    info!("Hello World!");
+{high_task}
 
+#[embassy_executor::task]
+async fn run_low({low_args}) {{
     loop {{
-        Timer::after(Duration::from_millis(500)).await;
-        info!("Hello!");
+{low_loop}
     }}
+}}
+
+#[entry]
+fn main() -> ! {{
+{init}{persisted}{hello}{setup}{inits}
+
+    interrupt::{irq}.set_priority(Priority::P2);
+    let spawner = EXECUTOR_HIGH.start(interrupt::{irq});
+    {spawn_high}
+
+    let executor = EXECUTOR_LOW.init(Executor::new());
+    executor.run(|spawner| {{
+        {spawn_low}
+    }})
 }}"#
-                ),
-            }],
+            );
+
+            return Self::src_dir(cfg, main, modules);
+        }
+
+        let (items, spawner, setup, main_loop) = match &example {
+            Some(example) => {
+                let (spawner, spawn_button) = match &example.button_task {
+                    Some(_) => (
+                        "spawner",
+                        format!("\n    {}", spawn("watch_button(button)")),
+                    ),
+                    None if tasks.is_empty() => ("_spawner", String::new()),
+                    None => ("spawner", String::new()),
+                };
+                (
+                    example
+                        .button_task
+                        .as_ref()
+                        .map(|task| format!("\n{}\n\n{task}\n", statics.trim_start()))
+                        .unwrap_or_default(),
+                    spawner,
+                    format!("{}{spawn_button}", example.setup),
+                    example.blink.clone(),
+                )
+            }
+            None => (
+                String::new(),
+                match tasks.is_empty() {
+                    true => "_spawner",
+                    false => "spawner",
+                },
+                String::new(),
+                format!("        Timer::after(Duration::from_millis(500)).await;{tick}"),
+            ),
+        };
+        let setup = tasks
+            .iter()
+            .fold(format!("{setup}{inits}"), |all, task| format!("{all}\n    {task}"));
+
+        let main = format!(
+            r#"#![no_std]
+#![no_main]{features}
+
+{atomics}{imports}use embassy_executor::Spawner;
+{gpio}use embassy_time::{{Duration, Timer}};
+{crates}{mods}
+{items}
+#[embassy_executor::main]
+async fn main({spawner}: Spawner) {{
+{init}{persisted}{hello}{setup}
+
+    loop {{
+{main_loop}
+    }}
+}}"#
+        );
+
+        Self::src_dir(cfg, main, modules)
+    }
+
+    /// main.rs and the modules next to it, with the tasks added by `generate task` wired into
+    /// main.rs the way the command does it
+    fn src_dir(
+        cfg: &GeneratorConfig,
+        mut main: String,
+        mut modules: Vec<Template>,
+    ) -> anyhow::Result<Self> {
+        for task in &cfg.tasks {
+            let wired = tasks::wire(&main, &task.name, &task.call(), cfg.logging)
+                .map_err(|e| anyhow!("Could not add the task {} to main.rs: {e}", task.name))?;
+            main = wired.unwrap_or(main);
+            modules.push(Template::File {
+                name: format!("{}.rs", task.name),
+                content: task.render(cfg.logging),
+            });
+        }
+        let main = Template::File {
+            name: "main.rs".into(),
+            content: main,
+        };
+
+        Ok(Template::Dir {
+            name: "src".into(),
+            children: [main].into_iter().chain(modules).collect(),
         })
     }
 
@@ -183,21 +545,64 @@ async fn main(_spawner: Spawner) {{
         })
     }
 
-    async fn build_rs(_cfg: &GeneratorConfig) -> anyhow::Result<Self> {
+    async fn build_rs(cfg: &GeneratorConfig) -> anyhow::Result<Self> {
+        let defmt = match cfg.logging {
+            Logging::Defmt => "\n    println!(\"cargo:rustc-link-arg-bins=-Tdefmt.x\");",
+            Logging::Log | Logging::None => "",
+        };
+        let (search, persist) = match cfg.panic {
+            Panic::Persist => (
+                "    // panic_persist.x is next to this file\n    println!(\"cargo:rustc-link-search={}\", std::env::var(\"CARGO_MANIFEST_DIR\").unwrap());\n    println!(\"cargo:rerun-if-changed=panic_persist.x\");\n\n",
+                "\n    println!(\"cargo:rustc-link-arg-bins=-Tpanic_persist.x\");",
+            ),
+            Panic::Probe | Panic::Halt | Panic::Reset => ("", ""),
+        };
+
         Ok(Template::File {
             name: "build.rs".into(),
-            content: r#"fn main() {
-    println!("cargo:rustc-link-arg-bins=--nmagic");
-    println!("cargo:rustc-link-arg-bins=-Tlink.x");
-    println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
-}"#
-                .into(),
+            content: format!(
+                r#"fn main() {{
+{search}    println!("cargo:rustc-link-arg-bins=--nmagic");
+    println!("cargo:rustc-link-arg-bins=-Tlink.x");{defmt}{persist}
+}}"#
+            ),
         })
     }
 
     async fn app_cargo_toml(cfg: &GeneratorConfig) -> anyhow::Result<Self> {
         let name = cfg.name.as_str();
+        let license = match &cfg.license {
+            Some(license) => format!("\nlicense = {}", toml_edit::value(license.as_str())),
+            None => "".into(),
+        };
         let crate_decl = crate_declaration(cfg, !cfg.workspace).await?;
+        let logging = match cfg.logging {
+            Logging::Defmt => {
+                "\ndefmt = { version = \"0.3\" }\ndefmt-rtt = { version = \"0.4\" }\n".into()
+            }
+            Logging::Log => {
+                let level = cfg.log_level.to_possible_value().unwrap(); // should be safe
+                let level = level.get_name();
+                format!(
+                    "\nlog = {{ version = \"0.4.20\", features = [\"max_level_{level}\", \"release_max_level_{level}\"] }}\n\
+                     rtt-target = \"0.5\"\n"
+                )
+            }
+            Logging::None => "".into(),
+        };
+        let panic = match (cfg.panic, cfg.logging) {
+            (Panic::Probe, Logging::Defmt) => {
+                r#"panic-probe = { version = "0.3", features = ["print-defmt"] }"#
+            }
+            (Panic::Probe, _) => r#"panic-probe = { version = "0.3" }"#,
+            (Panic::Halt, _) => r#"panic-halt = { version = "0.2" }"#,
+            (Panic::Reset, _) => r#"panic-reset = { version = "0.1" }"#,
+            (Panic::Persist, _) => r#"panic-persist = { version = "0.3", features = ["utf8"] }"#,
+        };
+        let static_cell = match cfg.executor {
+            Executor::Interrupt => "\nstatic-cell = \"2\"",
+            Executor::Thread => "",
+        };
         let patch = if cfg.workspace {
             "".into()
         } else {
@@ -210,18 +615,15 @@ async fn main(_spawner: Spawner) {{
                 r#"[package]
 name = "{name}"
 version = "0.1.0"
-edition = "2021"
+edition = "2021"{license}
 
 [dependencies]
 {crate_decl}
 
-panic-probe = {{ version = "0.3" }}
-
-defmt = {{ version = "0.3" }}
-defmt-rtt = {{ version = "0.4" }}
-
+{panic}
+{logging}
 cortex-m = {{ version = "0.7.6", features = ["critical-section-single-core"] }}
-cortex-m-rt = "0.7.0"
+cortex-m-rt = "0.7.0"{static_cell}
 
 futures = {{ version = "0.3.17", default-features = false, features = ["async-await"] }}
 
@@ -230,8 +632,20 @@ futures = {{ version = "0.3.17", default-features = false, features = ["async-aw
         })
     }
 
+    async fn panic_persist_x(_cfg: &GeneratorConfig) -> anyhow::Result<Self> {
+        Ok(Template::File {
+            name: "panic_persist.x".into(),
+            content: r#"/* panic-persist keeps the last panic message in the top 1 KiB of RAM, below it is the stack */
+_panic_dump_end = ORIGIN(RAM) + LENGTH(RAM);
+_panic_dump_start = _panic_dump_end - 1K;
+_stack_start = _panic_dump_start;
+"#
+            .into(),
+        })
+    }
+
     async fn rust_toolchain(cfg: &GeneratorConfig) -> anyhow::Result<Self> {
-        let channel = Git::get_toolchain_channel().await?;
+        let channel = cfg.channel.as_str();
         let target = cfg.target.as_str();
 
         Ok(Template::File {
@@ -288,6 +702,12 @@ resolver = "2"
         })
     }
 
+    fn name(&self) -> &str {
+        match self {
+            Template::File { name, .. } | Template::Dir { name, .. } => name,
+        }
+    }
+
     fn flatten(&self, root_path: &Path) -> Vec<TemplateItem> {
         fn traverse(node: &Template, path: &Path, stack: &mut Vec<TemplateItem>) {
             match node {
@@ -327,72 +747,340 @@ fn vendor_to_crate(vendor: Vendor) -> String {
         .into()
 }
 
-async fn crate_declaration(cfg: &GeneratorConfig, is_crate_root: bool) -> anyhow::Result<String> {
-    let embassy_crate = vendor_to_crate(cfg.vendor);
-    let version = Git::get_crate_version(embassy_crate).await?;
-    let mcu = cfg.mcu.as_str();
-
-    let r = if is_crate_root {
-        let mut r = match cfg.vendor {
-            Vendor::St => format!(
-                r#"embassy-stm32 = {{ version = "{version}", features = ["nightly", "defmt", "time-driver-any", "{mcu}", "memory-x", "exti"] }}"#
-            ),
-            Vendor::Nrf => format!(
-                r#"embassy-nrf = {{ version = "{version}", features = ["nightly", "defmt", "{mcu}", "time-driver-rtc1", "gpiote"] }}"#
-            ),
-            Vendor::Rp => format!(
-                r#"embassy-rp = {{ version = "{version}", features = ["defmt", "nightly", "time-driver"] }}"#
-            ),
+/// embassy crates of a generated project, with the features enabled on each of them
+pub fn crate_features(
+    vendor: Vendor,
+    mcu: &str,
+    options: &CrateOptions,
+) -> Vec<(String, Vec<String>)> {
+    let time_driver = options.time_driver.as_str();
+    let tick = tick_feature(options.tick_hz);
+    let arch = format!("arch-{}", options.arch);
+    let arena = options
+        .task_arena_size
+        .map(|size| format!("task-arena-size-{size}"));
+    let mut executor_features = vec!["nightly", &arch, "executor-thread"];
+    if options.executor == Executor::Interrupt {
+        executor_features.push("executor-interrupt");
+    }
+    executor_features.push("integrated-timers");
+    executor_features.extend(arena.as_deref());
+    let hal_features: &[&str] = match vendor {
+        Vendor::St => &["nightly", "defmt", time_driver, mcu, "memory-x", "exti"],
+        Vendor::Nrf => &["nightly", "defmt", mcu, time_driver, "gpiote"],
+        Vendor::Rp => &["defmt", "nightly", time_driver],
+    };
+
+    let crates: [(String, &[&str]); 5] = [
+        (vendor_to_crate(vendor), hal_features),
+        ("embassy-executor".into(), &executor_features),
+        (
+            "embassy-time".into(),
+            &["defmt", "defmt-timestamp-uptime", &tick],
+        ),
+        ("embassy-sync".into(), &["defmt"]),
+        ("embassy-futures".into(), &[]),
+    ];
+
+    crates
+        .into_iter()
+        .map(|(name, features)| {
+            // every crate logging with defmt can log with log instead
+            let features = features
+                .iter()
+                .filter(|f| options.nightly || **f != "nightly")
+                .filter_map(|f| match (options.logging, *f) {
+                    (Logging::Defmt, f) => Some(f),
+                    (Logging::Log, "defmt") => Some("log"),
+                    (_, f) if f.starts_with("defmt") => None,
+                    (_, f) => Some(f),
+                })
+                .map(|f| f.to_string())
+                .collect();
+            (name, features)
+        })
+        .collect()
+}
+
+/// choices of a project that change the features of the embassy crates
+pub struct CrateOptions {
+    pub nightly: bool,
+    pub logging: Logging,
+    pub time_driver: String,
+    pub tick_hz: u64,
+    pub arch: String,
+    pub executor: Executor,
+    pub task_arena_size: Option<u32>,
+}
+
+impl CrateOptions {
+    /// what a project for `mcu` gets by default on stable
+    pub fn defaults(mcu: &str) -> anyhow::Result<Self> {
+        let time = DATA.time(mcu)?;
+        Ok(Self {
+            nightly: false,
+            logging: Logging::default(),
+            time_driver: time.default_driver().to_owned(),
+            tick_hz: time.tick_hz(),
+            arch: DATA.flavor(mcu)?.arch().to_owned(),
+            executor: Executor::default(),
+            task_arena_size: None,
+        })
+    }
+}
+
+impl From<&GeneratorConfig> for CrateOptions {
+    fn from(cfg: &GeneratorConfig) -> Self {
+        Self {
+            nightly: cfg.nightly(),
+            logging: cfg.logging,
+            time_driver: cfg.time_driver.clone(),
+            tick_hz: cfg.tick_hz,
+            arch: cfg.arch.clone(),
+            executor: cfg.executor,
+            task_arena_size: cfg.task_arena_size,
+        }
+    }
+}
+
+/// pieces of main.rs blinking the first LED of a board, and changing the blink rate with its
+/// first button when it has one
+struct BoardExample {
+    atomics: &'static str,
+    gpio: String,
+    statics: &'static str,
+    button_task: Option<String>,
+    /// `led` and `button`, at the start of main
+    setup: String,
+    /// body of the blink loop
+    blink: String,
+}
+
+fn board_example(cfg: &GeneratorConfig, board: &Board, hal: &str) -> Option<BoardExample> {
+    let led = board.leds().first()?;
+    let button = board.buttons().first();
+    let off = match led.active_low {
+        true => "Level::High",
+        false => "Level::Low",
+    };
+    let led_init = match cfg.vendor {
+        Vendor::St => format!("Output::new(p.{}, {off}, Speed::Low)", led.pin),
+        Vendor::Nrf => format!("Output::new(p.{}, {off}, OutputDrive::Standard)", led.pin),
+        Vendor::Rp => format!("Output::new(p.{}, {off})", led.pin),
+    };
+    let mut setup = format!(
+        "\n\n    // {} on {}\n    let mut led = {led_init};",
+        led.name, led.pin
+    );
+
+    let Some(button) = button else {
+        let gpio = match cfg.vendor {
+            Vendor::St => format!("use {hal}::gpio::{{Level, Output, Speed}};\n"),
+            Vendor::Nrf => format!("use {hal}::gpio::{{Level, Output, OutputDrive}};\n"),
+            Vendor::Rp => format!("use {hal}::gpio::{{Level, Output}};\n"),
         };
+        return Some(BoardExample {
+            atomics: "",
+            gpio,
+            statics: "",
+            button_task: None,
+            setup,
+            blink: "        led.toggle();\n        Timer::after(Duration::from_millis(500)).await;"
+                .into(),
+        });
+    };
+
+    let pull = format!("Pull::{}", button.pull.variant());
+    let (gpio, input, button_init) = match cfg.vendor {
+        Vendor::St => (
+            format!(
+                "use {hal}::exti::ExtiInput;\nuse {hal}::gpio::{{Level, Output, Pull, Speed}};\n"
+            ),
+            "ExtiInput",
+            format!("ExtiInput::new(p.{}, p.{}, {pull})", button.pin, button.exti()),
+        ),
+        Vendor::Nrf => (
+            format!("use {hal}::gpio::{{Input, Level, Output, OutputDrive, Pull}};\n"),
+            "Input",
+            format!("Input::new(p.{}, {pull})", button.pin),
+        ),
+        Vendor::Rp => (
+            format!("use {hal}::gpio::{{Input, Level, Output, Pull}};\n"),
+            "Input",
+            format!("Input::new(p.{}, {pull})", button.pin),
+        ),
+    };
+    let edge = match button.active_low {
+        true => "falling",
+        false => "rising",
+    };
+    let pressed = match cfg.logging {
+        Logging::Defmt | Logging::Log => format!(
+            "\n        info!(\"{} pressed, blinking every {{}} ms\", ms);",
+            button.name
+        ),
+        Logging::None => String::new(),
+    };
+    setup.push_str(&format!(
+        "\n    // {} on {}\n    let button = {button_init};",
+        button.name, button.pin
+    ));
+
+    Some(BoardExample {
+        atomics: "use core::sync::atomic::{AtomicU32, Ordering};\n\n",
+        gpio,
+        statics: "\n\n/// blink period of the LED in ms\n\
+                  static BLINK_MS: AtomicU32 = AtomicU32::new(500);",
+        button_task: Some(format!(
+            r#"/// switches the LED between blinking slowly and quickly when {name} is pressed
+#[embassy_executor::task]
+async fn watch_button(mut button: {input}<'static>) {{
+    loop {{
+        button.wait_for_{edge}_edge().await;
+        let ms = match BLINK_MS.load(Ordering::Relaxed) {{
+            500 => 100,
+            _ => 500,
+        }};
+        BLINK_MS.store(ms, Ordering::Relaxed);{pressed}
+
+        // let the contacts settle
+        Timer::after(Duration::from_millis(50)).await;
+    }}
+}}"#,
+            name = button.name
+        )),
+        setup,
+        blink: "        led.toggle();\n        \
+                let ms = BLINK_MS.load(Ordering::Relaxed);\n        \
+                Timer::after(Duration::from_millis(ms as u64)).await;"
+            .into(),
+    })
+}
+
+/// `log` backend of projects without defmt, printing over RTT like defmt-rtt does
+const LOGGER_RS: &str = r#"//! prints the records of `log` over RTT, where probe-rs shows them
 
-        let version_executor = Git::get_crate_version("embassy-executor").await?;
-        let version_time = Git::get_crate_version("embassy-time").await?;
-        let version_sync = Git::get_crate_version("embassy-sync").await?;
-        let version_futures = Git::get_crate_version("embassy-futures").await?;
-
-        r.push_str(&format!(r#"
-embassy-executor = {{ version = "{version_executor}", features = ["nightly", "arch-cortex-m", "executor-thread", "integrated-timers"] }}
-embassy-time = {{ version = "{version_time}", features = ["defmt", "defmt-timestamp-uptime", "tick-hz-32_768"] }}
-embassy-sync = {{ version = "{version_sync}", features = ["defmt"] }}
-embassy-futures = {{ version = "{version_futures}" }}"#));
-
-        r
-    } else {
-        let mut r: String = match cfg.vendor {
-            Vendor::St => r#"embassy-stm32 = { workspace = true }"#.into(),
-            Vendor::Nrf => r#"embassy-nrf = { workspace = true }"#.into(),
-            Vendor::Rp => r#"embassy-rp = { workspace = true }"#.into(),
+use log::{LevelFilter, Log, Metadata, Record};
+use rtt_target::{rprintln, rtt_init_print};
+
+struct RttLogger;
+
+impl Log for RttLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        rprintln!("{} {}", record.level(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: RttLogger = RttLogger;
+
+/// sets up the RTT channel and installs the logger, the level is capped by the
+/// `max_level_*` features of log
+pub fn init() {
+    rtt_init_print!();
+    // SAFETY: called once at the start of main, before any other code can log
+    unsafe {
+        log::set_logger_racy(&LOGGER).unwrap();
+        log::set_max_level_racy(LevelFilter::Trace);
+    }
+}
+"#;
+
+/// statement spawning `task`, failing loudly when its pool is full
+pub fn spawn(spawner: &str, task: &str, logging: Logging) -> String {
+    match logging {
+        Logging::Defmt => format!("unwrap!({spawner}.spawn({task}));"),
+        Logging::Log | Logging::None => format!("{spawner}.spawn({task}).unwrap();"),
+    }
+}
+
+/// crate providing the panic handler
+fn panic_crate(panic: Panic) -> &'static str {
+    match panic {
+        Panic::Probe => "panic-probe",
+        Panic::Halt => "panic-halt",
+        Panic::Reset => "panic-reset",
+        Panic::Persist => "panic-persist",
+    }
+}
+
+pub fn runner(runner: Runner, mcu: &str) -> String {
+    match runner {
+        Runner::ProbeRun => format!("probe-run --chip {mcu} --speed 1000 --connect-under-reset"),
+        Runner::ProbeRs => format!("probe-rs run --chip {mcu} --speed 1000 --connect-under-reset"),
+    }
+}
+
+/// [`crate_features`] with the extra crates and features asked for
+pub fn project_crates(cfg: &GeneratorConfig) -> Vec<(String, Vec<String>)> {
+    let mut crates = crate_features(cfg.vendor, &cfg.mcu, &cfg.into());
+    for krate in &cfg.crates {
+        if !crates.iter().any(|(name, _)| name == krate.name()) {
+            crates.push((krate.name().into(), vec![]));
+        }
+    }
+
+    for (krate, feature) in cfg.features.iter().filter_map(|f| f.split_once('/')) {
+        if let Some((_, features)) = crates.iter_mut().find(|(name, _)| name == krate) {
+            if !features.iter().any(|f| f == feature) {
+                features.push(feature.into());
+            }
+        }
+    }
+
+    crates
+}
+
+async fn crate_declaration(cfg: &GeneratorConfig, is_crate_root: bool) -> anyhow::Result<String> {
+    let mut lines = vec![];
+    for (name, features) in project_crates(cfg) {
+        let line = if !is_crate_root {
+            format!(r#"{name} = {{ workspace = true }}"#)
+        } else if features.is_empty() {
+            let version = crate_version(cfg, &name)?;
+            format!(r#"{name} = {{ version = "{version}" }}"#)
+        } else {
+            let version = crate_version(cfg, &name)?;
+            let features = quoted_list(&features);
+            format!(r#"{name} = {{ version = "{version}", features = [{features}] }}"#)
         };
+        lines.push(line);
+    }
 
-        r.push_str(
-            r#"
-embassy-executor = { workspace = true }
-embassy-time = { workspace = true }
-embassy-sync = { workspace = true }
-embassy-futures = { workspace = true }"#,
-        );
+    Ok(lines.join("\n"))
+}
 
-        r
-    };
+fn crate_version<'a>(cfg: &'a GeneratorConfig, name: &str) -> anyhow::Result<&'a str> {
+    cfg.versions
+        .get(name)
+        .map(String::as_str)
+        .ok_or(anyhow::anyhow!("No version resolved for {name}"))
+}
 
-    Ok(r)
+fn quoted_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|i| format!(r#""{i}""#))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 async fn crates_io_patch(cfg: &GeneratorConfig) -> anyhow::Result<String> {
-    let embassy_crate = vendor_to_crate(cfg.vendor);
-    let commit = if cfg.no_pin {
-        "".into()
-    } else {
-        let hash = Git::get_latest_commit().await?;
-        format!(r#"rev = "{hash}""#)
+    let upstream = cfg.upstream.as_str();
+    let commit = match &cfg.rev {
+        Some(rev) => format!(r#", rev = "{rev}""#),
+        None => "".into(),
     };
 
-    Ok(format!(
-        r#"[patch.crates-io]
-{embassy_crate} = {{ git = "https://github.com/embassy-rs/embassy", {commit} }}
-embassy-executor = {{ git = "https://github.com/embassy-rs/embassy", {commit} }}
-embassy-time = {{ git = "https://github.com/embassy-rs/embassy", {commit} }}
-embassy-sync = {{ git = "https://github.com/embassy-rs/embassy", {commit} }}
-embassy-futures = {{ git = "https://github.com/embassy-rs/embassy", {commit} }}"#
-    ))
+    let mut lines = vec!["[patch.crates-io]".to_owned()];
+    for (name, _) in project_crates(cfg) {
+        lines.push(format!(r#"{name} = {{ git = "{upstream}"{commit} }}"#));
+    }
+
+    Ok(lines.join("\n"))
 }
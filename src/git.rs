@@ -1,58 +1,55 @@
+use std::path::Path;
+
 use anyhow::anyhow;
 use toml::Table;
 
+pub const EMBASSY_GIT: &str = "https://github.com/embassy-rs/embassy";
+
 pub struct Git;
 
 impl Git {
     pub async fn get_latest_commit() -> anyhow::Result<String> {
-        let raw_commit = reqwest::get("https://github.com/embassy-rs/embassy/commit/main.patch")
-            .await?
-            .text()
-            .await?;
+        Self::get_commit("main").await
+    }
+
+    /// sha of the commit a tag, branch or (short) sha points at
+    pub async fn get_commit(reference: &str) -> anyhow::Result<String> {
+        let raw_commit = reqwest::get(format!(
+            "https://github.com/embassy-rs/embassy/commit/{reference}.patch"
+        ))
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
 
         let sha = raw_commit
             .lines()
             .find(|line| line.starts_with("From "))
-            .unwrap()
-            .split_whitespace()
-            .nth(1)
-            .ok_or(anyhow!("Could not find commit SHA"))?;
+            .and_then(|line| line.split_whitespace().nth(1))
+            .ok_or(anyhow!("Could not find the commit SHA of {reference}"))?;
 
         Ok(sha.to_owned())
     }
 
-    pub async fn get_toolchain_channel() -> anyhow::Result<String> {
-        let raw_toml_file = reqwest::get(
-            "https://raw.githubusercontent.com/embassy-rs/embassy/main/rust-toolchain.toml",
-        )
-        .await?
-        .text()
-        .await?;
-
-        let toolchain_file: RustToolchain = toml::from_str(&raw_toml_file)?;
+    pub async fn get_toolchain_channel(rev: &str) -> anyhow::Result<String> {
+        toolchain_file_channel(rev, "rust-toolchain.toml").await
+    }
 
-        Ok(toolchain_file._toolchain._channel)
+    /// channel of `rust-toolchain-nightly.toml`, kept by embassy revisions that build on stable
+    pub async fn get_nightly_toolchain_channel(rev: &str) -> anyhow::Result<String> {
+        toolchain_file_channel(rev, "rust-toolchain-nightly.toml").await
     }
 
-    pub async fn get_crate_version(name: impl Into<String>) -> anyhow::Result<String> {
+    pub async fn get_crate_version(name: impl Into<String>, rev: &str) -> anyhow::Result<String> {
         let name: String = name.into();
-
-        // exceptions:
-        // - "embassy-boot-*":
-        //      crates are not in the "embassy-boot" directory,
-        //      eg: dir for "embassy-boot-stm32" is "./embassy-boot/stm32"
-        let path = if name.starts_with("embassy-boot-") {
-            let subdir = name.split('-').nth(2).unwrap();
-            format!("embassy-boot/{}/Cargo.toml", subdir)
-        } else {
-            format!("{}/Cargo.toml", name)
-        };
+        let path = crate_manifest_path(&name);
 
         let raw_content = reqwest::get(&format!(
-            "https://raw.githubusercontent.com/embassy-rs/embassy/main/{}",
-            path
+            "https://raw.githubusercontent.com/embassy-rs/embassy/{}/{}",
+            rev, path
         ))
         .await?
+        .error_for_status()?
         .text()
         .await?;
 
@@ -60,6 +57,105 @@ impl Git {
 
         Ok(cargo_file._package._version)
     }
+
+    /// number of commits on main that are not in `rev`
+    pub async fn get_commits_behind(rev: &str) -> anyhow::Result<u64> {
+        let raw_json = reqwest::Client::builder()
+            .user_agent("embassy-cli")
+            .build()?
+            .get(format!(
+                "https://api.github.com/repos/embassy-rs/embassy/compare/{rev}...main"
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        let comparison: Comparison = serde_json::from_str(&raw_json)?;
+
+        Ok(comparison.ahead_by)
+    }
+
+    pub fn get_local_head(checkout: &Path) -> anyhow::Result<String> {
+        local_git(checkout, &["rev-parse", "HEAD"])
+    }
+
+    pub fn get_local_commits_behind(checkout: &Path, rev: &str) -> anyhow::Result<u64> {
+        Ok(local_git(checkout, &["rev-list", "--count", &format!("{rev}..HEAD")])?.parse()?)
+    }
+
+    pub fn get_local_toolchain_channel(checkout: &Path) -> anyhow::Result<String> {
+        local_toolchain_file_channel(&checkout.join("rust-toolchain.toml"))
+    }
+
+    pub fn get_local_nightly_toolchain_channel(checkout: &Path) -> anyhow::Result<String> {
+        local_toolchain_file_channel(&checkout.join("rust-toolchain-nightly.toml"))
+    }
+
+    pub fn get_local_crate_version(checkout: &Path, name: &str) -> anyhow::Result<String> {
+        let raw_content = std::fs::read_to_string(checkout.join(crate_manifest_path(name)))?;
+        let cargo_file: CrateManifest = toml::from_str(&raw_content)?;
+
+        Ok(cargo_file._package._version)
+    }
+}
+
+async fn toolchain_file_channel(rev: &str, file: &str) -> anyhow::Result<String> {
+    let raw_toml_file = reqwest::get(format!(
+        "https://raw.githubusercontent.com/embassy-rs/embassy/{rev}/{file}"
+    ))
+    .await?
+    .error_for_status()?
+    .text()
+    .await?;
+
+    let toolchain_file: RustToolchain = toml::from_str(&raw_toml_file)?;
+
+    Ok(toolchain_file._toolchain._channel)
+}
+
+fn local_toolchain_file_channel(path: &Path) -> anyhow::Result<String> {
+    let raw_toml_file = std::fs::read_to_string(path)?;
+    let toolchain_file: RustToolchain = toml::from_str(&raw_toml_file)?;
+
+    Ok(toolchain_file._toolchain._channel)
+}
+
+fn crate_manifest_path(name: &str) -> String {
+    // exceptions:
+    // - "embassy-boot-*":
+    //      crates are not in the "embassy-boot" directory,
+    //      eg: dir for "embassy-boot-stm32" is "./embassy-boot/stm32"
+    if name.starts_with("embassy-boot-") {
+        let subdir = name.split('-').nth(2).unwrap();
+        format!("embassy-boot/{}/Cargo.toml", subdir)
+    } else {
+        format!("{}/Cargo.toml", name)
+    }
+}
+
+fn local_git(checkout: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(checkout)
+        .args(args)
+        .output()?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_owned())
+}
+
+#[derive(serde::Deserialize)]
+struct Comparison {
+    ahead_by: u64,
 }
 
 #[derive(serde::Deserialize)]
@@ -1,9 +1,29 @@
 use clap::Parser;
-use commands::{completion::completions, create::create, Command};
+use commands::{
+    add::add,
+    completion::{complete_mcu, completions},
+    config::config,
+    create::create,
+    doctor::doctor,
+    generate::generate,
+    info::info,
+    list::list,
+    outdated::outdated,
+    preset::preset,
+    regen::regen,
+    update::update,
+    Command,
+};
+use error::ErrorKind;
+use generator::data::DataError;
 
 mod commands;
+mod config;
+mod error;
 mod generator;
 mod git;
+mod preset;
+mod project;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,9 +36,32 @@ pub struct Cli {
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    match cli.command {
-        Command::Create(cc) => create(cc).await?,
-        Command::Completion(cc) => completions(cc),
+    if let Err(err) = run(cli.command).await {
+        match err.downcast_ref::<DataError>() {
+            Some(data_err) => eprintln!("{}", data_err.render()),
+            None => eprintln!("Error: {err:?}"),
+        }
+        std::process::exit(ErrorKind::of(&err).exit_code());
+    }
+
+    Ok(())
+}
+
+async fn run(command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Create(cc) => create(*cc).await?,
+        Command::Preset(pc) => preset(pc)?,
+        Command::Add(ac) => add(ac).await?,
+        Command::Update(uc) => update(uc).await?,
+        Command::Regen(rc) => regen(rc).await?,
+        Command::Generate(gc) => generate(gc).await?,
+        Command::Outdated(oc) => outdated(oc).await?,
+        Command::Doctor(dc) => doctor(dc)?,
+        Command::List(lc) => list(lc)?,
+        Command::Info(ic) => info(ic)?,
+        Command::Config(cfg) => config(cfg)?,
+        Command::Completion(cc) => completions(cc)?,
+        Command::CompleteMcu(cc) => complete_mcu(cc)?,
     }
 
     Ok(())
@@ -0,0 +1,234 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{self, name_of, Config},
+    error::ValidationError,
+    generator::{
+        clocks::format_freq, generate::GeneratorConfig, peripherals::Peripheral, tasks::Task,
+    },
+};
+
+/// a named bundle of `create` options, stored as toml
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Preset {
+    pub vendor: Option<String>,
+    pub mcu: Option<String>,
+    pub board: Option<String>,
+    pub runner: Option<String>,
+    pub workspace: Option<bool>,
+    pub pin: Option<bool>,
+    pub upstream: Option<String>,
+    pub logging: Option<String>,
+    pub log_level: Option<String>,
+    pub panic: Option<String>,
+    pub executor: Option<String>,
+    pub editor: Option<String>,
+    pub license: Option<String>,
+    pub toolchain: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub crates: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    pub template: Option<PathBuf>,
+    pub time_driver: Option<String>,
+    pub tick_hz: Option<u64>,
+    pub executor_irq: Option<String>,
+    pub task_arena_size: Option<u32>,
+    pub hse: Option<String>,
+    pub sysclk: Option<String>,
+    /// resolved when the project is created, only `kind` and `instance` are needed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peripherals: Vec<Peripheral>,
+    /// added with `generate task`, in the order they were spawned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<Task>,
+}
+
+impl Preset {
+    /// loads a preset file, or a preset saved under `name` in the user config directory
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        let path = Path::new(name);
+        let path = match path.is_file() {
+            true => path.to_owned(),
+            false => {
+                let saved = presets_dir()
+                    .map(|dir| dir.join(format!("{name}.toml")))
+                    .filter(|path| path.is_file());
+                saved.ok_or_else(|| {
+                    let saved = list().unwrap_or_default();
+                    match saved.is_empty() {
+                        true => ValidationError(format!(
+                            "No preset file or saved preset named \"{name}\""
+                        )),
+                        false => ValidationError(format!(
+                            "No preset file or saved preset named \"{name}\", saved presets: {}",
+                            saved.join(", ")
+                        )),
+                    }
+                })?
+            }
+        };
+
+        let content = std::fs::read_to_string(&path)?;
+        let mut preset: Self = toml::from_str(&content)
+            .map_err(|e| ValidationError(format!("Invalid preset {}: {e}", path.display())))?;
+
+        // template directories are relative to the preset file
+        if let (Some(template), Some(dir)) = (&preset.template, path.parent()) {
+            preset.template = Some(dir.join(template));
+        }
+
+        Ok(preset)
+    }
+
+    /// saves the preset in the user config directory, returns where it was written
+    pub fn save(&self, name: &str) -> anyhow::Result<PathBuf> {
+        let dir = presets_dir().ok_or(anyhow!("Neither XDG_CONFIG_HOME nor HOME is set"))?;
+        std::fs::create_dir_all(&dir)?;
+
+        // relative to the presets, so the file still works where they are shared
+        let mut preset = self.clone();
+        preset.template = self.template.as_ref().map(|t| relative_path(t, &dir));
+
+        let path = dir.join(format!("{name}.toml"));
+        std::fs::write(&path, toml::to_string(&preset)?)?;
+
+        Ok(path)
+    }
+
+    /// options of `other` override those of `self`
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            vendor: other.vendor.or(self.vendor),
+            mcu: other.mcu.or(self.mcu),
+            board: other.board.or(self.board),
+            runner: other.runner.or(self.runner),
+            workspace: other.workspace.or(self.workspace),
+            pin: other.pin.or(self.pin),
+            upstream: other.upstream.or(self.upstream),
+            logging: other.logging.or(self.logging),
+            log_level: other.log_level.or(self.log_level),
+            panic: other.panic.or(self.panic),
+            executor: other.executor.or(self.executor),
+            editor: other.editor.or(self.editor),
+            license: other.license.or(self.license),
+            toolchain: other.toolchain.or(self.toolchain),
+            crates: or_list(self.crates, other.crates),
+            features: or_list(self.features, other.features),
+            template: other.template.or(self.template),
+            time_driver: other.time_driver.or(self.time_driver),
+            tick_hz: other.tick_hz.or(self.tick_hz),
+            executor_irq: other.executor_irq.or(self.executor_irq),
+            task_arena_size: other.task_arena_size.or(self.task_arena_size),
+            hse: other.hse.or(self.hse),
+            sysclk: other.sysclk.or(self.sysclk),
+            peripherals: or_list(self.peripherals, other.peripherals),
+            tasks: or_list(self.tasks, other.tasks),
+        }
+    }
+
+    /// the options shared with the config files, to be layered on top of them
+    pub fn config(&self) -> Config {
+        Config {
+            vendor: self.vendor.clone(),
+            runner: self.runner.clone(),
+            workspace: self.workspace,
+            pin: self.pin,
+            upstream: self.upstream.clone(),
+            logging: self.logging.clone(),
+            log_level: self.log_level.clone(),
+            panic: self.panic.clone(),
+            executor: self.executor.clone(),
+            editor: self.editor.clone(),
+            license: self.license.clone(),
+            toolchain: self.toolchain.clone(),
+            chip_data: None,
+        }
+    }
+}
+
+impl From<&GeneratorConfig> for Preset {
+    fn from(cfg: &GeneratorConfig) -> Self {
+        Self {
+            vendor: Some(cfg.vendor.into()),
+            mcu: Some(cfg.mcu.clone()),
+            board: cfg.board.clone(),
+            runner: Some(name_of(cfg.runner)),
+            workspace: Some(cfg.workspace),
+            pin: Some(!cfg.no_pin),
+            upstream: Some(cfg.upstream.clone()),
+            logging: Some(name_of(cfg.logging)),
+            log_level: Some(name_of(cfg.log_level)),
+            panic: Some(name_of(cfg.panic)),
+            executor: Some(name_of(cfg.executor)),
+            editor: Some(name_of(cfg.editor)),
+            license: cfg.license.clone(),
+            toolchain: cfg.toolchain.clone(),
+            crates: cfg.crates.iter().map(|c| c.name().to_owned()).collect(),
+            features: cfg.features.clone(),
+            template: cfg
+                .template
+                .as_ref()
+                .map(|t| t.canonicalize().unwrap_or(t.clone())),
+            time_driver: Some(cfg.time_driver.clone()),
+            tick_hz: Some(cfg.tick_hz),
+            executor_irq: cfg.executor_irq.clone(),
+            task_arena_size: cfg.task_arena_size,
+            hse: cfg.hse.map(format_freq),
+            sysclk: cfg.sysclk.map(format_freq),
+            peripherals: cfg.peripherals.clone(),
+            tasks: cfg.tasks.clone(),
+        }
+    }
+}
+
+fn or_list<T>(mine: Vec<T>, theirs: Vec<T>) -> Vec<T> {
+    match theirs.is_empty() {
+        true => mine,
+        false => theirs,
+    }
+}
+
+/// `path` relative to the directory `base`, or `path` as is when either does not exist
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let (Ok(path_abs), Ok(base)) = (path.canonicalize(), base.canonicalize()) else {
+        return path.to_owned();
+    };
+    let common = path_abs
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(path_abs.components().skip(common));
+    relative
+}
+
+/// `presets` next to the user config file
+pub fn presets_dir() -> Option<PathBuf> {
+    Some(config::user_path()?.parent()?.join("presets"))
+}
+
+/// names of the saved presets
+pub fn list() -> anyhow::Result<Vec<String>> {
+    let Some(dir) = presets_dir().filter(|dir| dir.is_dir()) else {
+        return Ok(vec![]);
+    };
+
+    let mut names = std::fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "toml"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>();
+    names.sort();
+
+    Ok(names)
+}
@@ -0,0 +1,299 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use toml_edit::{Array, Document, InlineTable, Item, Table, TableLike, Value};
+
+/// an existing project, either a single crate or a workspace created with `--workspace`
+pub struct Project {
+    pub root: PathBuf,
+    pub workspace: bool,
+}
+
+impl Project {
+    /// the nearest manifest, or the workspace it is a member of
+    pub fn discover(start: &Path) -> anyhow::Result<Self> {
+        let start = start.canonicalize()?;
+        let package = start
+            .ancestors()
+            .find(|dir| dir.join("Cargo.toml").exists())
+            .ok_or(anyhow!(
+                "No Cargo.toml found in {} or any parent directory",
+                start.display()
+            ))?;
+        let manifest = read_manifest(&package.join("Cargo.toml"))?;
+        if manifest.contains_key("workspace") {
+            return Ok(Self {
+                root: package.to_owned(),
+                workspace: true,
+            });
+        }
+
+        // the way cargo finds the workspace of a package
+        let explicit = manifest
+            .get("package")
+            .and_then(|p| p.get("workspace"))
+            .and_then(|w| w.as_str());
+        let root = match explicit {
+            Some(root) => Some(package.join(root).canonicalize()?),
+            None => package
+                .ancestors()
+                .skip(1)
+                .find(|dir| {
+                    read_manifest(&dir.join("Cargo.toml"))
+                        .is_ok_and(|doc| doc.contains_key("workspace"))
+                })
+                .map(Path::to_owned),
+        };
+        let member = root.filter(|root| {
+            read_manifest(&root.join("Cargo.toml")).is_ok_and(|doc| is_member(&doc, root, package))
+        });
+
+        Ok(match member {
+            Some(root) => Self {
+                root,
+                workspace: true,
+            },
+            None => Self {
+                root: package.to_owned(),
+                workspace: false,
+            },
+        })
+    }
+
+    pub fn root_manifest(&self) -> PathBuf {
+        self.root.join("Cargo.toml")
+    }
+
+    /// manifest of the application crate, this is the root manifest outside of a workspace
+    pub fn app_manifest(&self) -> anyhow::Result<PathBuf> {
+        if !self.workspace {
+            return Ok(self.root_manifest());
+        }
+
+        let doc = read_manifest(&self.root_manifest())?;
+        let member = doc
+            .get("workspace")
+            .and_then(|w| w.get("default-members"))
+            .and_then(|m| m.as_array())
+            .and_then(|m| m.get(0))
+            .and_then(|m| m.as_str())
+            .unwrap_or("crates/app");
+
+        Ok(self.root.join(member).join("Cargo.toml"))
+    }
+
+    /// embassy revision the `[patch.crates-io]` entries are pinned to, if any
+    pub fn pinned_rev(&self) -> anyhow::Result<Option<String>> {
+        let doc = read_manifest(&self.root_manifest())?;
+
+        Ok(embassy_patches(&doc)
+            .into_iter()
+            .find_map(|(_, patch)| patch.get("rev").and_then(|r| r.as_str()).map(String::from)))
+    }
+
+    /// git repository the existing embassy patches point at, if any
+    pub fn upstream(&self) -> anyhow::Result<Option<String>> {
+        let doc = read_manifest(&self.root_manifest())?;
+
+        Ok(embassy_patches(&doc)
+            .into_iter()
+            .find_map(|(_, patch)| patch.get("git").and_then(|g| g.as_str()).map(String::from)))
+    }
+}
+
+/// whether `package` is listed in the `members` of the workspace at `root`, and not excluded
+fn is_member(doc: &Document, root: &Path, package: &Path) -> bool {
+    let Ok(relative) = package.strip_prefix(root) else {
+        return false;
+    };
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let matches = |key: &str| {
+        doc.get("workspace")
+            .and_then(|w| w.get(key))
+            .and_then(|m| m.as_array())
+            .is_some_and(|patterns| {
+                patterns
+                    .iter()
+                    .filter_map(|p| p.as_str())
+                    .any(|pattern| glob_match(pattern, &relative))
+            })
+    };
+
+    matches("members") && !matches("exclude")
+}
+
+/// matches a path against a `members` glob, `*` and `?` stay within a path component
+fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    let regex = regex::escape(pattern)
+        .replace(r"\*", "[^/]*")
+        .replace(r"\?", "[^/]");
+    regex::Regex::new(&format!("^{regex}$")).is_ok_and(|re| re.is_match(path))
+}
+
+pub fn read_manifest(path: &Path) -> anyhow::Result<Document> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Could not read {}: {e}", path.display()))?;
+
+    Ok(content.parse::<Document>()?)
+}
+
+pub fn write_manifest(path: &Path, doc: &Document) -> anyhow::Result<()> {
+    std::fs::write(path, doc.to_string())?;
+    Ok(())
+}
+
+/// `[patch.crates-io]` entries of embassy crates pulled from git
+pub fn embassy_patches(doc: &Document) -> Vec<(&str, &dyn TableLike)> {
+    let Some(patches) = doc
+        .get("patch")
+        .and_then(|p| p.get("crates-io"))
+        .and_then(|p| p.as_table_like())
+    else {
+        return vec![];
+    };
+
+    patches
+        .iter()
+        .filter_map(|(name, patch)| Some((name, patch.as_table_like()?)))
+        .filter(|(name, patch)| is_embassy_patch(name, *patch))
+        .collect()
+}
+
+pub fn is_embassy_patch(name: &str, patch: &dyn TableLike) -> bool {
+    name.starts_with("embassy-") && patch.contains_key("git")
+}
+
+/// dependency tables of a manifest, including `[workspace.dependencies]` and the
+/// `[target.'cfg(..)'.dependencies]` ones
+pub fn dependency_tables(doc: &mut Document) -> Vec<&mut dyn TableLike> {
+    let mut tables = vec![];
+    for (key, item) in doc.as_table_mut().iter_mut() {
+        match key.get() {
+            key if DEPENDENCY_KEYS.contains(&key) => tables.extend(item.as_table_like_mut()),
+            "workspace" => tables.extend(
+                item.as_table_like_mut()
+                    .and_then(|w| w.get_mut("dependencies"))
+                    .and_then(|d| d.as_table_like_mut()),
+            ),
+            "target" => {
+                let targets = item
+                    .as_table_like_mut()
+                    .into_iter()
+                    .flat_map(|t| t.iter_mut());
+                for (_, target) in targets {
+                    let target = target
+                        .as_table_like_mut()
+                        .into_iter()
+                        .flat_map(|t| t.iter_mut());
+                    for (key, deps) in target {
+                        if DEPENDENCY_KEYS.contains(&key.get()) {
+                            tables.extend(deps.as_table_like_mut());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    tables
+}
+
+const DEPENDENCY_KEYS: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// embassy crates a manifest depends on, with their version requirement if they have one
+pub fn embassy_dependencies(doc: &Document) -> Vec<(String, Option<String>)> {
+    let targets = doc
+        .get("target")
+        .and_then(|t| t.as_table_like())
+        .into_iter()
+        .flat_map(|t| t.iter())
+        .map(|(_, target)| target)
+        .collect::<Vec<_>>();
+    let tables = DEPENDENCY_KEYS
+        .iter()
+        .flat_map(|key| {
+            [doc.as_item()]
+                .into_iter()
+                .chain(targets.iter().copied())
+                .filter_map(move |parent| parent.get(key))
+        })
+        .chain(doc.get("workspace").and_then(|w| w.get("dependencies")));
+
+    let mut dependencies: Vec<(String, Option<String>)> = vec![];
+    for (name, dep) in tables
+        .filter_map(|deps| deps.as_table_like())
+        .flat_map(|deps| deps.iter())
+        .filter(|(name, _)| name.starts_with("embassy-"))
+    {
+        let version = match dep.as_str() {
+            Some(version) => Some(version),
+            None => dep.get("version").and_then(|v| v.as_str()),
+        };
+        // a crate in several tables is reported once
+        if !dependencies.iter().any(|(n, _)| n == name) {
+            dependencies.push((name.to_owned(), version.map(String::from)));
+        }
+    }
+    dependencies
+}
+
+/// replaces a string value while keeping its formatting, returns the old value
+pub fn set_string(value: &mut Value, new: &str) -> Option<String> {
+    let old = value.as_str().map(String::from);
+    let decor = value.decor().clone();
+    *value = Value::from(new);
+    *value.decor_mut() = decor;
+    old
+}
+
+/// returns the table at `path`, creating missing tables along the way
+pub fn table_mut<'a>(doc: &'a mut Document, path: &[&str]) -> anyhow::Result<&'a mut Table> {
+    let mut table = doc.as_table_mut();
+    for key in path {
+        let item = table.entry(key).or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        });
+        table = item
+            .as_table_mut()
+            .ok_or(anyhow!("`{}` is not a table", path.join(".")))?;
+    }
+
+    Ok(table)
+}
+
+/// inserts a dependency, merging its fields and features into an existing entry
+pub fn upsert_dependency(deps: &mut Table, name: &str, entry: InlineTable) {
+    let mut merged = match deps.get(name) {
+        Some(Item::Value(Value::String(version))) => {
+            let mut table = InlineTable::new();
+            table.insert("version", version.value().as_str().into());
+            table
+        }
+        Some(Item::Value(Value::InlineTable(table))) => table.clone(),
+        Some(Item::Table(table)) => table.clone().into_inline_table(),
+        _ => InlineTable::new(),
+    };
+
+    for (key, value) in entry {
+        if key.as_str() == "features" {
+            let features = merged
+                .entry("features")
+                .or_insert(Value::Array(Array::new()));
+            if let (Some(existing), Some(new)) = (features.as_array_mut(), value.as_array()) {
+                for feature in new.iter().filter_map(|f| f.as_str()) {
+                    if !existing.iter().any(|f| f.as_str() == Some(feature)) {
+                        existing.push(feature);
+                    }
+                }
+            }
+        } else {
+            merged.insert(key, value);
+        }
+    }
+
+    merged.fmt();
+    deps.insert(name, toml_edit::value(merged));
+}